@@ -7,7 +7,7 @@ use thread::DatagenThread;
 
 use monty::{
     chess::ChessState,
-    mcts::MctsParams,
+    mcts::{MctsParams, Priors, RootSelection, SearchOptions},
     networks::{self, PolicyNetwork, ValueNetwork},
     read_into_struct_unchecked, uci, MappedWeights,
 };
@@ -128,6 +128,14 @@ pub fn run_datagen(
 ) {
     println!("{opts:#?}");
 
+    let options = SearchOptions {
+        priors: opts.priors,
+        root_selection: opts.root_selection,
+        temperature: opts.temperature,
+        temperature_decay_plies: opts.temperature_decay_plies,
+        ..Default::default()
+    };
+
     let stop_base = AtomicBool::new(false);
     let stop = &stop_base;
 
@@ -157,7 +165,15 @@ pub fn run_datagen(
             let this_book = book.clone();
             let this_dest = dest_mutex.clone();
             s.spawn(move || {
-                let mut thread = DatagenThread::new(params.clone(), stop, this_book, this_dest);
+                let mut thread = DatagenThread::new(
+                    params.clone(),
+                    options,
+                    stop,
+                    this_book,
+                    opts.opening_plies,
+                    opts.random_plies,
+                    this_dest,
+                );
                 thread.run(opts.nodes, opts.policy_data, policy, value);
             });
         }
@@ -173,13 +189,24 @@ pub struct RunOptions {
     games: usize,
     threads: usize,
     book: Option<String>,
+    /// Plies played from the built-in opening lines, when there is no book.
+    opening_plies: usize,
+    /// Random moves played after the opening (or one more).
+    random_plies: usize,
     policy_data: bool,
+    priors: Priors,
+    root_selection: RootSelection,
+    temperature: f32,
+    temperature_decay_plies: u32,
     nodes: usize,
     out_path: String,
 }
 
 pub fn parse_args(args: Args) -> Option<RunOptions> {
-    let mut opts = RunOptions::default();
+    let mut opts = RunOptions {
+        random_plies: 8,
+        ..Default::default()
+    };
 
     let mut mode = 0;
 
@@ -187,11 +214,17 @@ pub fn parse_args(args: Args) -> Option<RunOptions> {
         match arg.as_str() {
             "bench" => return None,
             "--policy-data" => opts.policy_data = true,
+            "--gumbel" => opts.root_selection = RootSelection::Gumbel,
             "-t" | "--threads" => mode = 1,
             "-b" | "--book" => mode = 2,
             "-n" | "--nodes" => mode = 3,
             "-o" | "--output" => mode = 4,
             "-g" | "--games" => mode = 5,
+            "-p" | "--priors" => mode = 6,
+            "--temperature" => mode = 7,
+            "--temperature-decay" => mode = 8,
+            "--openings" => mode = 9,
+            "--random-plies" => mode = 10,
             _ => match mode {
                 1 => {
                     opts.threads = arg.parse().expect("can't parse");
@@ -213,6 +246,31 @@ pub fn parse_args(args: Args) -> Option<RunOptions> {
                     opts.games = arg.parse().expect("can't parse");
                     mode = 0;
                 }
+                6 => {
+                    opts.priors = match arg.as_str() {
+                        "network" => Priors::Network,
+                        "heuristic" => Priors::Heuristic,
+                        "uniform" => Priors::Uniform,
+                        _ => panic!("unrecognised priors {arg}"),
+                    };
+                    mode = 0;
+                }
+                7 => {
+                    opts.temperature = arg.parse().expect("can't parse");
+                    mode = 0;
+                }
+                8 => {
+                    opts.temperature_decay_plies = arg.parse().expect("can't parse");
+                    mode = 0;
+                }
+                9 => {
+                    opts.opening_plies = arg.parse().expect("can't parse");
+                    mode = 0;
+                }
+                10 => {
+                    opts.random_plies = arg.parse().expect("can't parse");
+                    mode = 0;
+                }
                 _ => println!("unrecognised argument {arg}"),
             },
         }
@@ -2,8 +2,9 @@ use crate::{Destination, Rand};
 
 use monty::{
     chess::{ChessState, GameState},
-    mcts::{Limits, MctsParams, Searcher},
+    mcts::{Limits, MctsParams, SearchOptions, Searcher},
     networks::{PolicyNetwork, ValueNetwork},
+    openings,
     tree::Tree,
 };
 use montyformat::{MontyFormat, MontyValueFormat, SearchData};
@@ -16,24 +17,33 @@ use std::sync::{
 pub struct DatagenThread<'a> {
     rng: Rand,
     params: MctsParams,
+    options: SearchOptions,
     dest: Arc<Mutex<Destination>>,
     stop: &'a AtomicBool,
     book: Option<Vec<&'a str>>,
+    opening_plies: usize,
+    random_plies: usize,
 }
 
 impl<'a> DatagenThread<'a> {
     pub fn new(
         params: MctsParams,
+        options: SearchOptions,
         stop: &'a AtomicBool,
         book: Option<Vec<&'a str>>,
+        opening_plies: usize,
+        random_plies: usize,
         dest: Arc<Mutex<Destination>>,
     ) -> Self {
         Self {
             rng: Rand::with_seed(),
             params,
+            options,
             dest,
             stop,
             book,
+            opening_plies,
+            random_plies,
         }
     }
 
@@ -63,12 +73,15 @@ impl<'a> DatagenThread<'a> {
         let mut position = if let Some(book) = &self.book {
             let idx = self.rng.rand_int() as usize % book.len();
             ChessState::from_fen(book[idx])
+        } else if self.opening_plies > 0 {
+            let rng = &mut self.rng;
+            openings::random_opening(self.opening_plies, |n| rng.rand_int() as usize % n)
         } else {
             ChessState::from_fen(ChessState::STARTPOS)
         };
 
-        // play 8 or 9 random moves
-        for _ in 0..(8 + (self.rng.rand_int() % 2)) {
+        // play `random_plies` or one more random moves
+        for _ in 0..(self.random_plies + (self.rng.rand_int() % 2) as usize) {
             let mut moves = Vec::new();
             position.map_legal_moves(|mov| moves.push(mov));
 
@@ -93,6 +106,7 @@ impl<'a> DatagenThread<'a> {
             max_nodes: node_limit,
             max_time: None,
             opt_time: None,
+            max_mate: None,
         };
 
         let mut result = 0.5;
@@ -124,6 +138,8 @@ impl<'a> DatagenThread<'a> {
 
         let mut policy_game = MontyFormat::new(montyformat_position, montyformat_castling);
 
+        let mut ply = 0;
+
         // play out game
         loop {
             if self.stop.load(Ordering::Relaxed) {
@@ -132,9 +148,14 @@ impl<'a> DatagenThread<'a> {
 
             let abort = AtomicBool::new(false);
             tree.set_root_position(&position);
-            let searcher = Searcher::new(&tree, &self.params, policy, value, &abort);
+            let searcher = Searcher::new(&tree, &self.params, &self.options, policy, value, &abort);
 
-            let (bm, score) = searcher.search(1, limits, false, &mut 0);
+            let search_result = searcher.search(1, limits, false, &mut 0);
+            let uniform = (self.rng.rand_int() >> 8) as f32 / (1 << 24) as f32;
+            let temperature = self.options.temperature_at(ply);
+            let bm = search_result.sample_move(temperature, uniform);
+            let score = search_result.q;
+            ply += 1;
 
             let best_move = montyformat::chess::Move::from(u16::from(bm));
 
@@ -148,7 +169,7 @@ impl<'a> DatagenThread<'a> {
             } else {
                 let mut dist = Vec::new();
 
-                let actions = { *tree[tree.root_node()].actions() };
+                let actions = tree[tree.root_node()].actions();
 
                 for action in 0..tree[tree.root_node()].num_actions() {
                     let node = &tree[actions + action];
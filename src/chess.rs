@@ -1,15 +1,25 @@
 mod attacks;
 mod board;
 pub mod consts;
+mod epd;
 mod frc;
 mod moves;
+mod pgn;
 
 use crate::{
     mcts::MctsParams,
     networks::{Accumulator, PolicyNetwork, ValueNetwork, POLICY_L1},
+    score,
 };
 
-pub use self::{attacks::Attacks, board::Board, frc::Castling, moves::Move};
+pub use self::{
+    attacks::Attacks,
+    board::{Board, FenError},
+    epd::EpdRecord,
+    frc::Castling,
+    moves::{Move, MoveList},
+    pgn::{parse_pgn, PgnError, PgnGame},
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub enum GameState {
@@ -86,15 +96,58 @@ impl ChessState {
         mov.to_uci(&self.castling)
     }
 
+    pub fn conv_mov_to_san(&self, mov: Move) -> String {
+        mov.to_san(&self.board, &self.castling)
+    }
+
+    /// The legal move written as `uci`, in UCI notation.
+    pub fn parse_uci(&self, uci: &str) -> Option<Move> {
+        let mut found = None;
+
+        self.map_legal_moves(|mov| {
+            if self.conv_mov_to_str(mov) == uci {
+                found = Some(mov);
+            }
+        });
+
+        found
+    }
+
+    /// The legal move written as `san`, ignoring check and annotation
+    /// suffixes (e.g. `Nf3+`, `exd5!?`), also accepting `0-0` for castling.
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        let strip = |s: &str| s.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+
+        let san = strip(san);
+        let mut found = None;
+
+        self.map_legal_moves(|mov| {
+            if strip(&self.conv_mov_to_san(mov)) == san {
+                found = Some(mov);
+            }
+        });
+
+        found
+    }
+
+    pub fn as_fen(&self) -> String {
+        self.board.as_fen(&self.castling)
+    }
+
+    /// Panics if `fen` is invalid, see `try_from_fen` for untrusted input.
     pub fn from_fen(fen: &str) -> Self {
+        Self::try_from_fen(fen).unwrap_or_else(|err| panic!("invalid fen '{fen}': {err}"))
+    }
+
+    pub fn try_from_fen(fen: &str) -> Result<Self, FenError> {
         let mut castling = Castling::default();
-        let board = Board::parse_fen(fen, &mut castling);
+        let board = Board::parse_fen(fen, &mut castling)?;
 
-        Self {
+        Ok(Self {
             board,
             castling,
             stack: Vec::new(),
-        }
+        })
     }
 
     pub fn map_legal_moves<F: FnMut(Move)>(&self, f: F) {
@@ -135,30 +188,67 @@ impl ChessState {
         policy.get(&self.board, &mov, hl)
     }
 
+    /// Policy logit of a move from simple move ordering heuristics,
+    /// for use in place of (or alongside) the policy network.
+    pub fn get_heuristic_policy(&self, mov: Move) -> f32 {
+        use consts::{Piece, SEE_VALS};
+
+        let mut logit = 0.0;
+
+        if mov.is_capture() {
+            let victim = if mov.is_en_passant() {
+                Piece::PAWN
+            } else {
+                self.board.get_pc(1 << mov.to())
+            };
+
+            logit += 1.0 + SEE_VALS[victim] as f32 / 1000.0;
+        }
+
+        if mov.is_promo() {
+            logit += if mov.promo_pc() == Piece::QUEEN {
+                2.0
+            } else {
+                -1.0
+            };
+        }
+
+        if !self.board.see(&mov, 0) {
+            logit -= 1.5;
+        }
+
+        let mut child = self.board;
+        child.make(mov, &self.castling);
+
+        if child.in_check() {
+            logit += 1.0;
+        }
+
+        logit
+    }
+
     #[cfg(not(feature = "datagen"))]
     fn piece_count(&self, piece: usize) -> i32 {
         self.board.piece(piece).count_ones() as i32
     }
 
-    pub fn get_value(&self, value: &ValueNetwork, _params: &MctsParams) -> i32 {
-        const K: f32 = 400.0;
-        let (win, draw, _) = value.eval(&self.board);
-
-        let score = win + draw / 2.0;
-        let cp = (-K * (1.0 / score.clamp(0.0, 1.0) - 1.0).ln()) as i32;
+    /// Internal centipawns (at `score::EVAL_SCALE`), reading the network's
+    /// output at the `eval_scale` param and then scaling by material.
+    pub fn get_value(&self, value: &ValueNetwork, params: &MctsParams) -> i32 {
+        let cp = score::q_to_eval_cp(value.eval(&self.board).q(), params.eval_scale());
 
         #[cfg(not(feature = "datagen"))]
         {
             use consts::Piece;
 
-            let mut mat = self.piece_count(Piece::KNIGHT) * _params.knight_value()
-                + self.piece_count(Piece::BISHOP) * _params.bishop_value()
-                + self.piece_count(Piece::ROOK) * _params.rook_value()
-                + self.piece_count(Piece::QUEEN) * _params.queen_value();
+            let mut mat = self.piece_count(Piece::KNIGHT) * params.knight_value()
+                + self.piece_count(Piece::BISHOP) * params.bishop_value()
+                + self.piece_count(Piece::ROOK) * params.rook_value()
+                + self.piece_count(Piece::QUEEN) * params.queen_value();
 
-            mat = _params.material_offset() + mat / _params.material_div1();
+            mat = params.material_offset() + mat / params.material_div1();
 
-            cp * mat / _params.material_div2()
+            cp * mat / params.material_div2()
         }
 
         #[cfg(feature = "datagen")]
@@ -166,7 +256,7 @@ impl ChessState {
     }
 
     pub fn get_value_wdl(&self, value: &ValueNetwork, params: &MctsParams) -> f32 {
-        1.0 / (1.0 + (-(self.get_value(value, params) as f32) / 400.0).exp())
+        score::eval_cp_to_q(self.get_value(value, params), score::EVAL_SCALE)
     }
 
     pub fn perft(&self, depth: usize) -> u64 {
@@ -244,6 +334,20 @@ impl ChessState {
         }
 
         println!("+-----------------+");
+
+        let fen = self.as_fen();
+        let fields = fen.split_whitespace().collect::<Vec<_>>();
+
+        println!("FEN: {fen}");
+        println!("Side to move: {}", ["white", "black"][self.stm()]);
+        println!("Castling rights: {}", fields[2]);
+        println!("En passant: {}", fields[3]);
+        println!("Key: {:016x}", self.hash());
+        println!("Legal moves: {}", moves.len());
+        println!(
+            "In check: {}",
+            if self.board.in_check() { "yes" } else { "no" }
+        );
     }
 }
 
@@ -275,3 +379,70 @@ fn perft<const ROOT: bool, const BULK: bool>(pos: &Board, depth: u8, castling: &
 
     count
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SAN of each UCI move in `fen`, checking that it parses back.
+    fn san(fen: &str, uci: &str) -> String {
+        let pos = ChessState::from_fen(fen);
+        let mov = pos.parse_uci(uci).unwrap();
+        let san = pos.conv_mov_to_san(mov);
+
+        assert_eq!(pos.parse_san(&san), Some(mov), "{san}");
+        san
+    }
+
+    #[test]
+    fn san_round_trip() {
+        for fen in [
+            ChessState::STARTPOS,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let pos = ChessState::from_fen(fen);
+
+            pos.map_legal_moves(|mov| {
+                let san = pos.conv_mov_to_san(mov);
+                assert_eq!(pos.parse_san(&san), Some(mov), "{fen}: {san}");
+            });
+        }
+    }
+
+    #[test]
+    fn san_disambiguation() {
+        let fen = "7k/8/8/R7/8/8/8/R4R1K w - - 0 1";
+        assert_eq!(san(fen, "a1d1"), "Rad1");
+        assert_eq!(san(fen, "f1d1"), "Rfd1");
+        assert_eq!(san(fen, "a1a3"), "R1a3");
+        assert_eq!(san(fen, "a5a3"), "R5a3");
+
+        let fen = "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1";
+        assert_eq!(san(fen, "h4e1"), "Qh4e1");
+        assert_eq!(san(fen, "e4e1"), "Qee1");
+        assert_eq!(san(fen, "h1e1"), "Q1e1");
+    }
+
+    #[test]
+    fn san_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let pos = ChessState::from_fen(fen);
+
+        assert_eq!(san(fen, "e1g1"), "O-O");
+        assert_eq!(san(fen, "e1c1"), "O-O-O");
+        assert_eq!(pos.parse_san("0-0"), pos.parse_uci("e1g1"));
+        assert_eq!(pos.parse_san("O-O-O+"), pos.parse_uci("e1c1"));
+    }
+
+    #[test]
+    fn san_promotion() {
+        let fen = "1n5k/P7/8/8/8/8/8/K7 w - - 0 1";
+        let pos = ChessState::from_fen(fen);
+
+        assert_eq!(san(fen, "a7a8q"), "a8=Q");
+        assert_eq!(san(fen, "a7a8n"), "a8=N");
+        assert_eq!(san(fen, "a7b8r"), "axb8=R+");
+        assert_eq!(pos.parse_san("axb8=R"), pos.parse_uci("a7b8r"));
+    }
+}
@@ -7,6 +7,36 @@ use super::{
     moves::{serialise, Move},
 };
 
+/// Why a FEN string could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    Missing(&'static str),
+    Placement(String),
+    PieceCount(String),
+    SideToMove(String),
+    Castling(String),
+    EnPassant(String),
+    Counter(String),
+    OpponentInCheck,
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(field) => write!(f, "missing {field}"),
+            Self::Placement(msg) => write!(f, "invalid piece placement: {msg}"),
+            Self::PieceCount(msg) => write!(f, "invalid pieces: {msg}"),
+            Self::SideToMove(stm) => write!(f, "invalid side to move '{stm}'"),
+            Self::Castling(rights) => write!(f, "invalid castling rights '{rights}'"),
+            Self::EnPassant(sq) => write!(f, "invalid en passant square '{sq}'"),
+            Self::Counter(counter) => write!(f, "invalid move counter '{counter}'"),
+            Self::OpponentInCheck => write!(f, "side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
 #[repr(C)]
 #[derive(Copy, Clone, Default, PartialEq, Eq)]
 pub struct Board {
@@ -29,7 +59,7 @@ impl Board {
         halfm: u8,
         fullm: u16,
     ) -> Self {
-        Self {
+        let mut board = Self {
             bb,
             hash: 0,
             phase: 0,
@@ -38,7 +68,10 @@ impl Board {
             rights,
             halfm,
             fullm,
-        }
+        };
+
+        board.hash = board.compute_hash();
+        board
     }
 
     #[must_use]
@@ -75,15 +108,59 @@ impl Board {
         self.fullm
     }
 
+    /// Zobrist hash, kept up to date by `make`.
     #[must_use]
     pub fn hash(&self) -> u64 {
-        let mut hash = self.hash;
+        self.hash
+    }
+
+    /// Part of the hash from the side to move, castling rights and en
+    /// passant square.
+    fn state_hash(&self) -> u64 {
+        let mut hash = ZVALS.cr[usize::from(self.rights)] ^ ZVALS.c[self.stm()];
 
         if self.enp_sq > 0 {
             hash ^= ZVALS.enp[self.enp_sq as usize & 7];
         }
 
-        hash ^ ZVALS.cr[usize::from(self.rights)] ^ ZVALS.c[self.stm()]
+        hash
+    }
+
+    /// Hash recomputed from scratch, which `hash` should always match.
+    fn compute_hash(&self) -> u64 {
+        let mut hash = self.state_hash();
+
+        for side in [Side::WHITE, Side::BLACK] {
+            for piece in Piece::PAWN..=Piece::KING {
+                let mut bb = self.bb[side] & self.bb[piece];
+
+                while bb > 0 {
+                    let sq = bb.trailing_zeros() as usize;
+                    hash ^= ZVALS.pcs[side][piece][sq];
+                    bb &= bb - 1;
+                }
+            }
+        }
+
+        hash
+    }
+
+    /// Zobrist hash of just the pawns and side to move.
+    #[must_use]
+    pub fn pawn_hash(&self) -> u64 {
+        let mut hash = ZVALS.c[self.stm()];
+
+        for side in [Side::WHITE, Side::BLACK] {
+            let mut pawns = self.bb[side] & self.bb[Piece::PAWN];
+
+            while pawns > 0 {
+                let sq = pawns.trailing_zeros() as usize;
+                hash ^= ZVALS.pcs[side][Piece::PAWN][sq];
+                pawns &= pawns - 1;
+            }
+        }
+
+        hash
     }
 
     // POSITION INFO
@@ -407,6 +484,7 @@ impl Board {
         };
 
         // updating state
+        self.hash ^= self.state_hash();
         self.stm = !self.stm;
         self.enp_sq = 0;
         self.rights &= castling.mask(usize::from(mov.to())) & castling.mask(usize::from(mov.src()));
@@ -447,60 +525,173 @@ impl Board {
             }
             _ => {}
         }
+
+        self.hash ^= self.state_hash();
+        debug_assert_eq!(self.hash, self.compute_hash());
     }
 
     // CREATE POSITION
 
-    #[must_use]
-    pub fn parse_fen(fen: &str, castling: &mut Castling) -> Self {
+    pub fn parse_fen(fen: &str, castling: &mut Castling) -> Result<Self, FenError> {
         let mut pos = Self::default();
         let vec: Vec<&str> = fen.split_whitespace().collect();
-        let p: Vec<char> = vec[0].chars().collect();
+        let placement = vec.first().ok_or(FenError::Missing("piece placement"))?;
 
         // board
-        let (mut row, mut col) = (7, 0);
-        for ch in p {
-            if ch == '/' {
-                row -= 1;
-                col = 0;
-            } else if ('1'..='8').contains(&ch) {
-                col += ch.to_string().parse::<i16>().unwrap_or(0);
-            } else {
-                let idx: usize = "PNBRQKpnbrqk"
-                    .chars()
-                    .position(|element| element == ch)
-                    .unwrap_or(6);
-                let colour = usize::from(idx > 5);
-                let pc = idx + 2 - 6 * colour;
-                pos.toggle(colour, pc, (8 * row + col) as u16);
-                pos.phase += PHASE_VALS[pc];
-                col += 1;
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::Placement(format!(
+                "expected 8 ranks, found {}",
+                ranks.len()
+            )));
+        }
+
+        for (i, rank) in ranks.iter().enumerate() {
+            let row = 7 - i;
+            let mut col = 0;
+
+            for ch in rank.chars() {
+                if col >= 8 {
+                    return Err(FenError::Placement(format!("rank {} is too long", row + 1)));
+                }
+
+                if let Some(empty) = ch.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    col += empty as usize;
+                } else {
+                    let idx = "PNBRQKpnbrqk"
+                        .chars()
+                        .position(|element| element == ch)
+                        .ok_or_else(|| FenError::Placement(format!("invalid piece '{ch}'")))?;
+
+                    let colour = usize::from(idx > 5);
+                    let pc = idx + 2 - 6 * colour;
+                    pos.toggle(colour, pc, (8 * row + col) as u16);
+                    pos.phase += PHASE_VALS[pc];
+                    col += 1;
+                }
+            }
+
+            if col != 8 {
+                return Err(FenError::Placement(format!(
+                    "rank {} has {col} squares",
+                    row + 1
+                )));
+            }
+        }
+
+        for (side, name) in [(Side::WHITE, "white"), (Side::BLACK, "black")] {
+            let kings = (pos.piece(side) & pos.piece(Piece::KING)).count_ones();
+            let pawns = (pos.piece(side) & pos.piece(Piece::PAWN)).count_ones();
+            let pieces = pos.piece(side).count_ones();
+
+            if kings != 1 {
+                return Err(FenError::PieceCount(format!("{name} has {kings} kings")));
+            }
+
+            if pawns > 8 || pieces > 16 {
+                return Err(FenError::PieceCount(format!(
+                    "{name} has {pieces} pieces and {pawns} pawns"
+                )));
             }
         }
 
+        if pos.piece(Piece::PAWN) & 0xFF00_0000_0000_00FF != 0 {
+            return Err(FenError::PieceCount(
+                "pawns on the first or last rank".to_string(),
+            ));
+        }
+
         // side to move
-        pos.stm = vec[1] == "b";
+        pos.stm = match vec.get(1) {
+            Some(&"w") => false,
+            Some(&"b") => true,
+            Some(stm) => return Err(FenError::SideToMove(stm.to_string())),
+            None => return Err(FenError::Missing("side to move")),
+        };
 
         // castle rights
-        pos.rights = castling.parse(&pos, vec[2]);
+        let rights_str = vec.get(2).copied().unwrap_or("-");
+        let valid_chars = rights_str
+            .chars()
+            .all(|ch| "KQkqABCDEFGHabcdefgh".contains(ch));
+
+        if rights_str != "-" && !valid_chars {
+            return Err(FenError::Castling(rights_str.to_string()));
+        }
+
+        pos.rights = castling.parse(&pos, rights_str);
+
+        for (right, side, ks) in [
+            (Right::WKS, Side::WHITE, 1),
+            (Right::WQS, Side::WHITE, 0),
+            (Right::BKS, Side::BLACK, 1),
+            (Right::BQS, Side::BLACK, 0),
+        ] {
+            if pos.rights & right == 0 {
+                continue;
+            }
+
+            let back_rank = 56 * side;
+            let king = pos.king_sq(side);
+            let rook = back_rank + usize::from(castling.rook_file(side, ks));
+
+            let king_ok = if castling.is_chess960() {
+                king / 8 == back_rank / 8
+            } else {
+                king == back_rank + 4
+            };
+
+            let rook_ok = pos.piece(side) & pos.piece(Piece::ROOK) & (1 << rook) > 0;
+
+            if !king_ok || !rook_ok {
+                return Err(FenError::Castling(rights_str.to_string()));
+            }
+        }
 
         // en passant square
-        pos.enp_sq = if vec[3] == "-" {
+        let enp_str = vec.get(3).copied().unwrap_or("-");
+        pos.enp_sq = if enp_str == "-" {
             0
         } else {
-            let chs: Vec<char> = vec[3].chars().collect();
-            8 * chs[1].to_string().parse::<u8>().unwrap_or(0) + chs[0] as u8 - 105
+            let chs: Vec<u8> = enp_str.bytes().collect();
+            // white to move captures onto the 6th rank, and vice versa
+            let (rank, pushed) = if pos.stm { (b'3', 8) } else { (b'6', -8) };
+
+            if chs.len() != 2 || !(b'a'..=b'h').contains(&chs[0]) || chs[1] != rank {
+                return Err(FenError::EnPassant(enp_str.to_string()));
+            }
+
+            let sq = 8 * (chs[1] - b'1') + chs[0] - b'a';
+            let pawn = (i32::from(sq) + pushed) as usize;
+            let opp = usize::from(!pos.stm);
+
+            if pos.piece(opp) & pos.piece(Piece::PAWN) & (1 << pawn) == 0 {
+                return Err(FenError::EnPassant(enp_str.to_string()));
+            }
+
+            sq
         };
 
         if let Some(hfm) = vec.get(4) {
-            pos.halfm = hfm.parse().unwrap_or(0);
+            pos.halfm = hfm
+                .parse()
+                .map_err(|_| FenError::Counter(hfm.to_string()))?;
         }
 
-        if let Some(fm) = vec.get(5) {
-            pos.fullm = fm.parse().unwrap_or(1);
+        pos.fullm = match vec.get(5) {
+            Some(fm) => fm.parse().map_err(|_| FenError::Counter(fm.to_string()))?,
+            None => 1,
+        };
+
+        let mut opp_to_move = pos;
+        opp_to_move.stm = !pos.stm;
+        if opp_to_move.in_check() {
+            return Err(FenError::OpponentInCheck);
         }
 
-        pos
+        pos.hash = pos.compute_hash();
+
+        Ok(pos)
     }
 
     pub fn map_legal_moves<F: FnMut(Move)>(&self, castling: &Castling, mut f: F) {
@@ -822,7 +1013,7 @@ impl Board {
         }
     }
 
-    pub fn as_fen(&self) -> String {
+    pub fn as_fen(&self, castling: &Castling) -> String {
         const PIECES: [char; 12] = ['P', 'N', 'B', 'R', 'Q', 'K', 'p', 'n', 'b', 'r', 'q', 'k'];
         let mut fen = String::new();
 
@@ -859,16 +1050,41 @@ impl Board {
 
         if self.rights == 0 {
             fen.push('-');
-        } else {
-            let mut r = self.rights;
-            while r > 0 {
-                let q = r.trailing_zeros();
-                r &= r - 1;
-                fen.push(['k', 'q', 'K', 'Q'][q as usize]);
+        }
+
+        for (right, side, ks) in [
+            (Right::WKS, Side::WHITE, 1),
+            (Right::WQS, Side::WHITE, 0),
+            (Right::BKS, Side::BLACK, 1),
+            (Right::BQS, Side::BLACK, 0),
+        ] {
+            if self.rights & right == 0 {
+                continue;
             }
+
+            let ch = if castling.is_chess960() {
+                (b'a' + castling.rook_file(side, ks) as u8) as char
+            } else {
+                ['q', 'k'][ks]
+            };
+
+            fen.push(if side == Side::WHITE {
+                ch.to_ascii_uppercase()
+            } else {
+                ch
+            });
+        }
+
+        fen.push(' ');
+
+        if self.enp_sq == 0 {
+            fen.push('-');
+        } else {
+            fen.push((b'a' + self.enp_sq % 8) as char);
+            fen.push((b'1' + self.enp_sq / 8) as char);
         }
 
-        fen.push_str(" - 0 1");
+        fen.push_str(&format!(" {} {}", self.halfm, self.fullm));
 
         fen
     }
@@ -932,3 +1148,79 @@ fn btwn(bit1: u64, bit2: u64) -> u64 {
     let min = bit1.min(bit2);
     (bit1.max(bit2) - min) ^ min
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(fen: &str) -> Result<Board, FenError> {
+        Board::parse_fen(fen, &mut Castling::default())
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2k5/8/8/8/8/5K2/8 b - - 47 90",
+        ] {
+            let mut castling = Castling::default();
+            let board = Board::parse_fen(fen, &mut castling).unwrap();
+            assert_eq!(board.as_fen(&castling), fen);
+        }
+    }
+
+    #[test]
+    fn fen_errors() {
+        let cases = [
+            ("", FenError::Missing("piece placement")),
+            ("4k3/8/8/8/8/8/8/4K3", FenError::Missing("side to move")),
+            (
+                "4k3/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::Placement("expected 8 ranks, found 7".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3N w - - 0 1",
+                FenError::Placement("rank 1 is too long".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2 w - - 0 1",
+                FenError::Placement("rank 1 has 7 squares".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4X3 w - - 0 1",
+                FenError::Placement("invalid piece 'X'".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/8 w - - 0 1",
+                FenError::PieceCount("white has 0 kings".to_string()),
+            ),
+            (
+                "P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::PieceCount("pawns on the first or last rank".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+                FenError::SideToMove("x".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+                FenError::Castling("K".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+                FenError::EnPassant("e6".to_string()),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+                FenError::Counter("x".to_string()),
+            ),
+            ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", FenError::OpponentInCheck),
+        ];
+
+        for (fen, err) in cases {
+            assert_eq!(parse(fen).err(), Some(err), "{fen}");
+        }
+    }
+}
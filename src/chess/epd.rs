@@ -0,0 +1,126 @@
+//! Reading of EPD records, as used by test suites: a position without
+//! move counters, followed by operations such as `bm Nf3; id "WAC.001";`.
+
+use super::{ChessState, FenError, Move};
+
+pub struct EpdRecord {
+    pub pos: ChessState,
+    /// Opcodes and their operands, with quotes removed from strings.
+    pub ops: Vec<(String, Vec<String>)>,
+}
+
+impl EpdRecord {
+    pub fn parse(line: &str) -> Result<Self, FenError> {
+        let mut fields = line.trim().splitn(5, ' ');
+        let fen = fields.by_ref().take(4).collect::<Vec<_>>().join(" ");
+        let rest = fields.next().unwrap_or("");
+
+        let mut ops = Vec::new();
+
+        for op in split_ops(rest) {
+            let mut operands = split_operands(&op);
+
+            if !operands.is_empty() {
+                let opcode = operands.remove(0);
+                ops.push((opcode, operands));
+            }
+        }
+
+        let mut record = Self {
+            pos: ChessState::default(),
+            ops,
+        };
+
+        let halfmove = record.op("hmvc").and_then(|v| v.first()).map_or("0", |v| v);
+        let fullmove = record.op("fmvn").and_then(|v| v.first()).map_or("1", |v| v);
+        record.pos = ChessState::try_from_fen(&format!("{fen} {halfmove} {fullmove}"))?;
+
+        Ok(record)
+    }
+
+    pub fn op(&self, opcode: &str) -> Option<&[String]> {
+        self.ops
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// The legal moves given (in SAN) as operands of `opcode`, e.g. `bm`.
+    pub fn moves(&self, opcode: &str) -> Vec<Move> {
+        self.op(opcode)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|san| self.pos.parse_san(san))
+            .collect()
+    }
+
+    /// Points for each move in the `STS` format of the `c0` comment,
+    /// e.g. `c0 "Nf3=10, Nd2=5";`.
+    pub fn move_points(&self) -> Vec<(Move, u32)> {
+        let Some(comment) = self.op("c0").and_then(|v| v.first()) else {
+            return Vec::new();
+        };
+
+        comment
+            .split(',')
+            .filter_map(|entry| {
+                let (san, points) = entry.trim().split_once('=')?;
+                Some((self.pos.parse_san(san)?, points.trim().parse().ok()?))
+            })
+            .collect()
+    }
+}
+
+/// Splits at semicolons outside of quoted strings.
+fn split_ops(text: &str) -> Vec<String> {
+    let mut ops = Vec::new();
+    let mut op = String::new();
+    let mut in_string = false;
+
+    for ch in text.chars() {
+        match ch {
+            '"' => in_string = !in_string,
+            ';' if !in_string => {
+                ops.push(std::mem::take(&mut op));
+                continue;
+            }
+            _ => {}
+        }
+
+        op.push(ch);
+    }
+
+    ops.push(op);
+    ops
+}
+
+/// Splits at whitespace outside of quoted strings, removing the quotes.
+fn split_operands(op: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut operand = String::new();
+    let mut in_string = false;
+
+    for ch in op.chars() {
+        match ch {
+            '"' => {
+                if in_string {
+                    operands.push(std::mem::take(&mut operand));
+                }
+
+                in_string = !in_string;
+            }
+            _ if ch.is_whitespace() && !in_string => {
+                if !operand.is_empty() {
+                    operands.push(std::mem::take(&mut operand));
+                }
+            }
+            _ => operand.push(ch),
+        }
+    }
+
+    if !operand.is_empty() {
+        operands.push(operand);
+    }
+
+    operands
+}
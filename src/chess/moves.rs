@@ -1,6 +1,10 @@
 use crate::pop_lsb;
 
-use super::{consts::Flag, frc::Castling};
+use super::{
+    board::Board,
+    consts::{Flag, Piece},
+    frc::Castling,
+};
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Move {
@@ -79,6 +83,80 @@ impl Move {
 
         format!("{}{}{}", idx_to_sq(self.src()), idx_to_sq(to), promo)
     }
+
+    /// Standard Algebraic Notation of the move, which must be legal in `pos`.
+    pub fn to_san(self, pos: &Board, castling: &Castling) -> String {
+        let idx_to_sq = |i| format!("{}{}", ((i & 7) as u8 + b'a') as char, (i / 8) + 1);
+        let file = |i: u16| ((i & 7) as u8 + b'a') as char;
+        let rank = |i: u16| ((i / 8) as u8 + b'1') as char;
+
+        let mut san = String::new();
+
+        match self.flag() {
+            Flag::KS => san.push_str("O-O"),
+            Flag::QS => san.push_str("O-O-O"),
+            _ => {
+                let pc = pos.get_pc(1 << self.src());
+
+                if pc == Piece::PAWN {
+                    if self.is_capture() {
+                        san.push(file(self.src()));
+                    }
+                } else {
+                    san.push(['N', 'B', 'R', 'Q', 'K'][pc - Piece::KNIGHT]);
+
+                    // other pieces of the same type that can reach the square
+                    let mut ambiguous = false;
+                    let mut same_file = false;
+                    let mut same_rank = false;
+
+                    pos.map_legal_moves(castling, |mov| {
+                        if mov.to() == self.to()
+                            && mov.src() != self.src()
+                            && pos.get_pc(1 << mov.src()) == pc
+                        {
+                            ambiguous = true;
+                            same_file |= mov.src() & 7 == self.src() & 7;
+                            same_rank |= mov.src() / 8 == self.src() / 8;
+                        }
+                    });
+
+                    if ambiguous {
+                        if !same_file {
+                            san.push(file(self.src()));
+                        } else if !same_rank {
+                            san.push(rank(self.src()));
+                        } else {
+                            san.push(file(self.src()));
+                            san.push(rank(self.src()));
+                        }
+                    }
+                }
+
+                if self.is_capture() {
+                    san.push('x');
+                }
+
+                san.push_str(&idx_to_sq(self.to()));
+
+                if self.is_promo() {
+                    san.push('=');
+                    san.push(['N', 'B', 'R', 'Q'][self.promo_pc() - Piece::KNIGHT]);
+                }
+            }
+        }
+
+        let mut child = *pos;
+        child.make(self, castling);
+
+        if child.in_check() {
+            let mut has_moves = false;
+            child.map_legal_moves(castling, |_| has_moves = true);
+            san.push(if has_moves { '+' } else { '#' });
+        }
+
+        san
+    }
 }
 
 #[inline]
@@ -88,3 +166,67 @@ pub fn serialise<F: FnMut(Move)>(f: &mut F, mut attacks: u64, from: u16, flag: u
         f(Move::new(from, to, flag));
     }
 }
+
+/// Fixed-capacity list for the moves of a position (with e.g. their
+/// priors), kept on the stack so that expanding a node doesn't allocate.
+#[derive(Clone, Copy)]
+pub struct MoveList<T = Move> {
+    items: [T; MoveList::CAPACITY],
+    len: usize,
+}
+
+impl MoveList {
+    /// Legal positions have at most 218 moves.
+    pub const CAPACITY: usize = 256;
+}
+
+impl<T: Copy + Default> Default for MoveList<T> {
+    fn default() -> Self {
+        Self {
+            items: [T::default(); MoveList::CAPACITY],
+            len: 0,
+        }
+    }
+}
+
+impl<T: Copy> MoveList<T> {
+    /// Items past the capacity, only possible in illegal positions, are
+    /// dropped.
+    pub fn push(&mut self, item: T) {
+        if self.len < MoveList::CAPACITY {
+            self.items[self.len] = item;
+            self.len += 1;
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        let mut kept = 0;
+
+        for i in 0..self.len {
+            if f(&self.items[i]) {
+                self.items[kept] = self.items[i];
+                kept += 1;
+            }
+        }
+
+        self.len = kept;
+    }
+}
+
+impl<T> std::ops::Deref for MoveList<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items[..self.len]
+    }
+}
+
+impl<T> std::ops::DerefMut for MoveList<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.items[..self.len]
+    }
+}
@@ -0,0 +1,260 @@
+//! Reading of games in PGN, as far as needed to replay them: tags and the
+//! moves of the main line. Comments, variations and NAGs are skipped.
+
+use super::{ChessState, FenError, Move};
+
+/// A game of a PGN file, with the moves of its main line.
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// Position the game starts from, the `FEN` tag if present.
+    pub start: ChessState,
+    pub moves: Vec<Move>,
+    /// Game termination marker, e.g. `1-0`, `*` if missing.
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum PgnError {
+    Fen(FenError),
+    /// The SAN of a move that is not legal (or is ambiguous), and its ply.
+    IllegalMove(String, usize),
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fen(err) => write!(f, "invalid FEN tag: {err}"),
+            Self::IllegalMove(san, ply) => write!(f, "illegal move {san} at ply {ply}"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// Tags and movetext tokens of a game, before the moves are replayed.
+#[derive(Default)]
+struct RawGame {
+    tags: Vec<(String, String)>,
+    sans: Vec<String>,
+    result: Option<String>,
+}
+
+impl RawGame {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.sans.is_empty() && self.result.is_none()
+    }
+
+    fn replay(self) -> Result<PgnGame, PgnError> {
+        let fen = self
+            .tags
+            .iter()
+            .find(|(tag, _)| tag == "FEN")
+            .map_or(ChessState::STARTPOS, |(_, value)| value.as_str());
+
+        let start = ChessState::try_from_fen(fen).map_err(PgnError::Fen)?;
+        let mut pos = start.clone();
+        let mut moves = Vec::with_capacity(self.sans.len());
+
+        for (ply, san) in self.sans.into_iter().enumerate() {
+            let mov = pos
+                .parse_san(&san)
+                .ok_or(PgnError::IllegalMove(san, ply + 1))?;
+
+            pos.make_move(mov);
+            moves.push(mov);
+        }
+
+        Ok(PgnGame {
+            tags: self.tags,
+            start,
+            moves,
+            result: self.result.unwrap_or_else(|| "*".to_string()),
+        })
+    }
+}
+
+/// All games in `text`, each of which failed to parse if it has an
+/// invalid `FEN` tag or an illegal move.
+pub fn parse_pgn(text: &str) -> Vec<Result<PgnGame, PgnError>> {
+    let mut games = Vec::new();
+    let mut game = RawGame::default();
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '[' => {
+                // tags after movetext start the next game
+                if !game.sans.is_empty() || game.result.is_some() {
+                    games.push(std::mem::take(&mut game).replay());
+                }
+
+                let mut tag = String::new();
+                let mut in_string = false;
+                let mut escaped = false;
+
+                for ch in chars.by_ref() {
+                    match ch {
+                        '"' if !escaped => in_string = !in_string,
+                        ']' if !in_string => break,
+                        _ => {}
+                    }
+
+                    escaped = in_string && ch == '\\' && !escaped;
+                    tag.push(ch);
+                }
+
+                if let Some((name, value)) = tag.trim().split_once(char::is_whitespace) {
+                    let value = value.trim();
+                    let value = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .unwrap_or(value)
+                        .replace("\\\"", "\"");
+                    game.tags.push((name.to_string(), value));
+                }
+            }
+            '{' => {
+                for ch in chars.by_ref() {
+                    if ch == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for ch in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => {
+                let mut depth = 1;
+
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        '{' => {
+                            for ch in chars.by_ref() {
+                                if ch == '}' {
+                                    break;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            _ if ch.is_whitespace() || ch == ')' => {}
+            _ => {
+                let mut token = ch.to_string();
+
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || "[]{}();".contains(ch) {
+                        break;
+                    }
+
+                    token.push(ch);
+                    chars.next();
+                }
+
+                match token.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                        game.result = Some(token);
+                        games.push(std::mem::take(&mut game).replay());
+                    }
+                    _ if token.starts_with('$') => {}
+                    _ => {
+                        // move numbers may be attached, e.g. `12.Nf3` or `12...Nf3`
+                        let san = token.rsplit('.').next().unwrap_or("");
+
+                        if !san.is_empty() && !san.chars().all(|ch| ch.is_ascii_digit()) {
+                            game.sans.push(san.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if !game.is_empty() {
+        games.push(game.replay());
+    }
+
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uci(game: &PgnGame) -> Vec<String> {
+        let mut pos = game.start.clone();
+
+        game.moves
+            .iter()
+            .map(|&mov| {
+                let uci = pos.conv_mov_to_str(mov);
+                pos.make_move(mov);
+                uci
+            })
+            .collect()
+    }
+
+    #[test]
+    fn comments_and_variations() {
+        let text = r#"
+[Event "The \"Open\""]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 (1... c5 2. Nf3 (2. c3 {Alapin}) d6) 2.Nf3 $1 Nc6 ; to the end of the line
+3. Bb5 a6!? 1-0
+
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+
+1. e4 Kd7 2. e5 *
+"#;
+
+        let games = parse_pgn(text);
+        assert_eq!(games.len(), 2);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("Event"), Some("The \"Open\""));
+        assert_eq!(game.tag("White"), Some("A"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(uci(game), ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"]);
+
+        let game = games[1].as_ref().unwrap();
+        assert_eq!(game.start.as_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(game.result, "*");
+        assert_eq!(uci(game), ["e2e4", "e8d7", "e4e5"]);
+    }
+
+    #[test]
+    fn errors() {
+        let games = parse_pgn("1. e4 e5 2. Ke3 *\n\n[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n*");
+        assert_eq!(games.len(), 2);
+
+        assert!(matches!(
+            &games[0],
+            Err(PgnError::IllegalMove(san, 3)) if san == "Ke3"
+        ));
+        assert!(matches!(&games[1], Err(PgnError::Fen(_))));
+    }
+}
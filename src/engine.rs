@@ -0,0 +1,101 @@
+//! An owned searcher for embedding Monty in other programs, e.g. GUIs and
+//! analysis pipelines, without going through UCI on stdin/stdout.
+//!
+//! ```ignore
+//! let mut engine = Engine::new(policy, value, SearchOptions::default());
+//! let result = engine.think(&ChessState::default(), Limits::nodes(10_000));
+//! println!("{}", result.best_move);
+//! ```
+
+use std::sync::atomic::AtomicBool;
+
+use crate::{
+    chess::ChessState,
+    mcts::{Limits, MctsParams, SearchOptions, SearchResult, Searcher},
+    networks::{PolicyNetwork, ValueNetwork},
+    pool,
+    tree::Tree,
+};
+
+pub struct Engine<'a> {
+    policy: &'a PolicyNetwork,
+    value: &'a ValueNetwork,
+    params: MctsParams,
+    options: SearchOptions,
+    tree: Tree,
+    threads: usize,
+}
+
+impl<'a> Engine<'a> {
+    /// An engine with a 64MB tree, searching on one thread.
+    pub fn new(policy: &'a PolicyNetwork, value: &'a ValueNetwork, options: SearchOptions) -> Self {
+        Self {
+            policy,
+            value,
+            params: MctsParams::default(),
+            options,
+            tree: Tree::new_mb(64, 1),
+            threads: 1,
+        }
+    }
+
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut SearchOptions {
+        &mut self.options
+    }
+
+    pub fn params_mut(&mut self) -> &mut MctsParams {
+        &mut self.params
+    }
+
+    /// Replace the tree with one of `mb` megabytes, discarding the current one.
+    pub fn set_hash_mb(&mut self, mb: usize) {
+        self.tree = Tree::new_mb(mb, self.threads);
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+        pool::resize(self.threads);
+    }
+
+    /// Forget previous searches, e.g. at the start of a new game.
+    pub fn clear(&mut self) {
+        self.tree.clear(self.threads);
+    }
+
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Search `pos` within `limits`, reusing the tree of the previous search
+    /// where possible.
+    pub fn think(&mut self, pos: &ChessState, limits: Limits) -> SearchResult {
+        self.think_until(pos, limits, &AtomicBool::new(false))
+    }
+
+    /// Like `think`, also stopping as soon as `abort` is set, e.g. from
+    /// another thread. The search sets `abort` itself when it finishes, so
+    /// it needs to be reset before it is reused.
+    pub fn think_until(
+        &mut self,
+        pos: &ChessState,
+        limits: Limits,
+        abort: &AtomicBool,
+    ) -> SearchResult {
+        self.tree.set_root_position_quiet(pos);
+
+        let searcher = Searcher::new(
+            &self.tree,
+            &self.params,
+            &self.options,
+            self.policy,
+            self.value,
+            abort,
+        );
+
+        searcher.search(self.threads, limits, false, &mut 0)
+    }
+}
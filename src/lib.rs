@@ -1,8 +1,21 @@
 pub mod chess;
+pub mod engine;
+pub mod log;
 pub mod mcts;
 pub mod networks;
+pub mod openings;
+pub mod opponent;
+pub mod pool;
+pub mod profile;
+pub mod quantise;
+pub mod rng;
+pub mod score;
+pub mod selftune;
+pub mod serve;
+pub mod strength;
 pub mod tree;
 pub mod uci;
+pub mod xboard;
 
 use memmap2::Mmap;
 
@@ -50,6 +63,19 @@ macro_rules! pop_lsb {
     };
 }
 
+/// Hint that `item` will be read soon, to start loading it into cache.
+#[inline(always)]
+pub fn prefetch<T>(item: &T) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>((item as *const T).cast());
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = item;
+}
+
 /// # Safety
 /// Object must be valid if fully zeroed.
 pub unsafe fn boxed_and_zeroed<T>() -> Box<T> {
@@ -0,0 +1,94 @@
+//! Diagnostics, written with `log!` rather than printed directly so that
+//! they don't clutter the UCI stream unless asked for.
+//!
+//! Messages at `Level::Warn` and above are always printed as
+//! `info string <target> <message>`, and the rest only after `debug on`.
+//! With `LogFile` set, every message is also appended to the file with
+//! its time, level and target, whether or not debugging is on.
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Instant,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+static DEBUG: AtomicBool = AtomicBool::new(false);
+static HAS_FILE: AtomicBool = AtomicBool::new(false);
+static FILE: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// `log!(Level, "target", "format", args..)`
+#[macro_export]
+macro_rules! log {
+    ($level:ident, $target:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($crate::log::Level::$level) {
+            $crate::log::write($crate::log::Level::$level, $target, format_args!($($arg)+));
+        }
+    };
+}
+
+/// `debug on`/`debug off`.
+pub fn set_debug(debug: bool) {
+    DEBUG.store(debug, Ordering::Relaxed);
+}
+
+/// `setoption name LogFile`, an empty path closing the current file.
+pub fn set_file(path: &str) -> io::Result<()> {
+    let mut file = FILE.lock().unwrap();
+
+    *file = if path.is_empty() {
+        None
+    } else {
+        let opened = OpenOptions::new().create(true).append(true).open(path)?;
+        Some(LineWriter::new(opened))
+    };
+
+    START.get_or_init(Instant::now);
+    HAS_FILE.store(file.is_some(), Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn enabled(level: Level) -> bool {
+    to_stdout(level) || HAS_FILE.load(Ordering::Relaxed)
+}
+
+fn to_stdout(level: Level) -> bool {
+    level <= Level::Warn || DEBUG.load(Ordering::Relaxed)
+}
+
+pub fn write(level: Level, target: &str, args: fmt::Arguments) {
+    if to_stdout(level) {
+        println!("info string {target} {args}");
+    }
+
+    if let Some(file) = FILE.lock().unwrap().as_mut() {
+        let elapsed = START.get_or_init(Instant::now).elapsed().as_millis();
+
+        // logging must never interrupt the search
+        let _ = writeln!(file, "{elapsed:>9} {:<5} {target} {args}", level.name());
+    }
+}
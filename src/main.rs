@@ -1,4 +1,11 @@
 fn main() {
+    // needs no networks but the one it is given
+    let mut args = std::env::args().skip(1);
+    if let Some("quantise") = args.next().as_deref() {
+        monty::quantise::run_with_args(args);
+        return;
+    }
+
     #[cfg(feature = "embed")]
     net::run();
 
@@ -11,9 +18,8 @@ mod net {
     use memmap2::Mmap;
     use monty::{
         chess::ChessState,
-        mcts::MctsParams,
         networks::{PolicyNetwork, ValueNetwork},
-        uci,
+        selftune, serve, uci,
     };
     use once_cell::sync::Lazy;
     use sha2::{Digest, Sha256};
@@ -219,56 +225,113 @@ mod net {
 
     pub fn run() {
         let mut args = std::env::args();
-        let arg1 = args.nth(1);
+        let mut arg1 = args.nth(1);
+        let params = uci::params_from_args(&mut arg1, &mut args);
 
         // Interpret the memory-mapped data as network structures
         let policy: &PolicyNetwork = unsafe { read_into_struct_unchecked(&NETWORKS.0) };
         let value: &ValueNetwork = unsafe { read_into_struct_unchecked(&NETWORKS.1) };
 
+        if let Some("serve") = arg1.as_deref() {
+            serve::run_with_args(args, policy, value);
+            return;
+        }
+
+        if let Some("selftune") = arg1.as_deref() {
+            selftune::run_with_args(args, policy, value);
+            return;
+        }
+
         if let Some("bench") = arg1.as_deref() {
-            uci::bench(
-                ChessState::BENCH_DEPTH,
-                policy,
-                value,
-                &MctsParams::default(),
-            );
+            uci::bench(ChessState::BENCH_DEPTH, policy, value, &params);
             return;
         }
 
-        uci::run(policy, value);
+        uci::run_with_params(policy, value, params);
     }
 }
 
 #[cfg(not(feature = "embed"))]
 mod nonet {
     use monty::{
-        chess::ChessState, mcts::MctsParams, networks, read_into_struct_unchecked, uci,
-        MappedWeights,
+        chess::ChessState,
+        networks::{self, safetensors::SafeTensors},
+        read_into_struct_unchecked, selftune, serve, uci, MappedWeights,
     };
 
+    /// A network from a raw memory image, or a `.safetensors` file.
+    enum Network<'a, T> {
+        Mapped(MappedWeights<'a, T>),
+        Owned(Box<T>),
+    }
+
+    impl<T: SafeTensors> Network<'_, T> {
+        /// Exits if the network is corrupted or can't be read.
+        fn load(path: &str, published_name: &str) -> Self {
+            let network = networks::checksum::verify(path, published_name).and_then(|()| {
+                if path.ends_with(".safetensors") {
+                    networks::safetensors::load(path).map(Self::Owned)
+                } else {
+                    Ok(Self::Mapped(unsafe { read_into_struct_unchecked(path) }))
+                }
+            });
+
+            network.unwrap_or_else(|err| {
+                eprintln!("failed to load {path}: {err}");
+                std::process::exit(1);
+            })
+        }
+
+        fn get(&self) -> &T {
+            match self {
+                Self::Mapped(mapped) => mapped.data,
+                Self::Owned(net) => net,
+            }
+        }
+    }
+
     pub fn run() {
         let mut args = std::env::args();
-        let arg1 = args.nth(1);
+        let mut arg1 = args.nth(1);
+        let params = uci::params_from_args(&mut arg1, &mut args);
+
+        let mut policy_path = networks::PolicyFileDefaultName.to_string();
+        let mut value_path = networks::ValueFileDefaultName.to_string();
+
+        // `--policy <file>` and `--value <file>`, in place of the defaults
+        loop {
+            match arg1.as_deref() {
+                Some("--policy") => policy_path = args.next().unwrap_or_default(),
+                Some("--value") => value_path = args.next().unwrap_or_default(),
+                _ => break,
+            }
 
-        let policy_mapped: MappedWeights<networks::PolicyNetwork> =
-            unsafe { read_into_struct_unchecked(networks::PolicyFileDefaultName) };
+            arg1 = args.next();
+        }
 
-        let value_mapped: MappedWeights<networks::ValueNetwork> =
-            unsafe { read_into_struct_unchecked(networks::ValueFileDefaultName) };
+        let policy_net =
+            Network::<networks::PolicyNetwork>::load(&policy_path, networks::PolicyFileDefaultName);
+        let value_net =
+            Network::<networks::ValueNetwork>::load(&value_path, networks::ValueFileDefaultName);
 
-        let policy = policy_mapped.data;
-        let value = value_mapped.data;
+        let policy = policy_net.get();
+        let value = value_net.get();
+
+        if let Some("serve") = arg1.as_deref() {
+            serve::run_with_args(args, policy, value);
+            return;
+        }
+
+        if let Some("selftune") = arg1.as_deref() {
+            selftune::run_with_args(args, policy, value);
+            return;
+        }
 
         if let Some("bench") = arg1.as_deref() {
-            uci::bench(
-                ChessState::BENCH_DEPTH,
-                policy,
-                value,
-                &MctsParams::default(),
-            );
+            uci::bench(ChessState::BENCH_DEPTH, policy, value, &params);
             return;
         }
 
-        uci::run(policy, value);
+        uci::run_with_params(policy, value, params);
     }
 }
@@ -1,18 +1,32 @@
+mod corrhist;
+mod gumbel;
 mod helpers;
 mod iteration;
 mod params;
+mod result;
+mod sequencer;
 
+pub use corrhist::CorrHist;
 pub use helpers::SearchHelpers;
-pub use params::MctsParams;
+pub use params::{MctsParams, Tunable};
+pub use result::{PvMove, SearchResult, Variation};
+
+use gumbel::GumbelRoot;
+use sequencer::Sequencer;
 
 use crate::{
     chess::{GameState, Move},
+    log,
     networks::{PolicyNetwork, ValueNetwork},
+    score::{self, Score},
     tree::{NodePtr, Tree},
 };
 
 use std::{
-    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        RwLock,
+    },
     thread,
     time::Instant,
 };
@@ -23,6 +37,165 @@ pub struct Limits {
     pub opt_time: Option<u128>,
     pub max_depth: usize,
     pub max_nodes: usize,
+    /// Stop once a mate in at most this many moves is proven.
+    pub max_mate: Option<usize>,
+}
+
+impl Default for Limits {
+    /// No limits, searching until aborted.
+    fn default() -> Self {
+        Self {
+            max_time: None,
+            opt_time: None,
+            max_depth: 256,
+            max_nodes: i32::MAX as usize,
+            max_mate: None,
+        }
+    }
+}
+
+impl Limits {
+    pub fn nodes(max_nodes: usize) -> Self {
+        Self {
+            max_nodes,
+            ..Default::default()
+        }
+    }
+
+    /// Search for `ms` milliseconds.
+    pub fn movetime(ms: u128) -> Self {
+        Self {
+            max_time: Some(ms),
+            ..Default::default()
+        }
+    }
+}
+
+/// Source of the prior probabilities given to moves when a node is expanded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Priors {
+    /// Output of the policy network.
+    #[default]
+    Network,
+    /// Move ordering heuristics (captures, checks, SEE), no policy network needed.
+    Heuristic,
+    /// Every legal move is equally likely.
+    Uniform,
+}
+
+/// How moves are chosen at the root.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RootSelection {
+    /// PUCT, as in the rest of the tree, playing the best move by visits.
+    #[default]
+    Puct,
+    /// Sequential Halving with Gumbel noise (see `GumbelRoot`), which makes
+    /// better use of small node budgets.
+    Gumbel,
+}
+
+/// How search progress and the chosen move are reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Standard `info` and `bestmove` lines.
+    #[default]
+    Uci,
+    /// One JSON object per line, for programs consuming the output.
+    Json,
+}
+
+/// Search behaviour that is not tuned, but chosen by the user.
+#[derive(Clone, Copy, Debug)]
+pub struct SearchOptions {
+    pub priors: Priors,
+    pub root_selection: RootSelection,
+    /// Add children outside the root a few at a time, best prior first, as
+    /// their parent gains visits (see `SearchHelpers::get_widening_width`),
+    /// rather than all legal moves at once.
+    pub progressive_widening: bool,
+    /// Make multi-threaded searches with a node limit reproducible, by
+    /// ordering the playouts (see `Sequencer`), at a cost in speed.
+    pub repro_smp: bool,
+    /// Debugging mode of `repro_smp`, which also runs playouts one at a
+    /// time, handed to the threads in turn, so that concurrency bugs can be
+    /// reproduced and bisected with a node limit.
+    pub round_robin_smp: bool,
+    /// Amount by which draws are worth less than 0.5 to the side to move
+    /// at the root, in Q.
+    pub contempt: f32,
+    /// Standard deviations of Q added to the value of moves in selection,
+    /// positive for risk-seeking play (e.g. must-win games) and negative
+    /// for risk-averse play.
+    pub risk: f32,
+    /// Play a move sampled from the root visit distribution with this
+    /// temperature, rather than the most visited one (0 to disable).
+    pub temperature: f32,
+    /// Ply by which the temperature has decayed linearly to 0, or 0 to
+    /// keep it constant.
+    pub temperature_decay_plies: u32,
+    /// Stop a timed search early once the root visit distribution gains
+    /// less than this KL divergence per node (0 to disable).
+    pub min_kld_gain: f32,
+    /// Nodes between the root visit distributions that are compared.
+    pub kld_gain_interval: usize,
+    /// Deepest ply (from the root) that the tree is grown to, with nodes
+    /// there only evaluated, for deliberately shallow play.
+    pub max_tree_depth: Option<usize>,
+    /// Minimum time in ms between `info` lines, apart from those for a
+    /// large score change (0 for no limit).
+    pub report_interval: u64,
+    /// Report how much of the tree was inherited from the previous search
+    /// and how the root changed, after each search.
+    pub reuse_stats: bool,
+    /// Print PVs in SAN rather than UCI notation (`bestmove` is unaffected).
+    pub san_output: bool,
+    /// Q reported as 100 centipawns, so that reported scores mean the same
+    /// expected score with networks of different sharpness.
+    pub cp_100_q: f32,
+    pub output_format: OutputFormat,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            priors: Priors::default(),
+            root_selection: RootSelection::default(),
+            progressive_widening: false,
+            repro_smp: false,
+            round_robin_smp: false,
+            contempt: 0.0,
+            risk: 0.0,
+            temperature: 0.0,
+            temperature_decay_plies: 0,
+            min_kld_gain: 0.0,
+            kld_gain_interval: 100,
+            max_tree_depth: None,
+            report_interval: 0,
+            reuse_stats: false,
+            san_output: false,
+            cp_100_q: score::DEFAULT_CP_100_Q,
+            output_format: OutputFormat::Uci,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Temperature to sample the move played at game ply `ply` with.
+    pub fn temperature_at(&self, ply: u32) -> f32 {
+        if self.temperature_decay_plies == 0 {
+            return self.temperature;
+        }
+
+        let remaining = 1.0 - ply as f32 / self.temperature_decay_plies as f32;
+        self.temperature * remaining.max(0.0)
+    }
+}
+
+/// The last `info` line printed, for rate limiting output.
+#[cfg(not(feature = "uci-minimal"))]
+struct LastReport {
+    time: Instant,
+    line: Option<(Vec<Move>, Score)>,
 }
 
 #[derive(Default)]
@@ -32,20 +205,40 @@ pub struct SearchStats {
     pub main_iters: AtomicUsize,
     pub avg_depth: AtomicUsize,
     pub seldepth: AtomicUsize,
+    /// Milliseconds into the search when the best move last changed.
+    pub best_move_time: AtomicUsize,
+}
+
+/// Network evaluations made by a search, which differ from its playouts
+/// because of hash hits, terminal nodes and correction history samples.
+#[derive(Default)]
+struct EvalCounts {
+    value: AtomicUsize,
+    policy: AtomicUsize,
+    /// New nodes whose position an earlier search had already evaluated
+    /// (it left a hash entry), i.e. were rebuilt rather than inherited.
+    recomputed: AtomicUsize,
 }
 
 pub struct Searcher<'a> {
     tree: &'a Tree,
     params: &'a MctsParams,
+    options: &'a SearchOptions,
     policy: &'a PolicyNetwork,
     value: &'a ValueNetwork,
     abort: &'a AtomicBool,
+    /// Set up at the start of each search with `RootSelection::Gumbel`.
+    gumbel: RwLock<Option<GumbelRoot>>,
+    /// `Limits::max_mate` of the current search, `usize::MAX` if none.
+    max_mate: AtomicUsize,
+    evals: EvalCounts,
 }
 
 impl<'a> Searcher<'a> {
     pub fn new(
         tree: &'a Tree,
         params: &'a MctsParams,
+        options: &'a SearchOptions,
         policy: &'a PolicyNetwork,
         value: &'a ValueNetwork,
         abort: &'a AtomicBool,
@@ -53,9 +246,40 @@ impl<'a> Searcher<'a> {
         Self {
             tree,
             params,
+            options,
             policy,
             value,
             abort,
+            gumbel: RwLock::new(None),
+            max_mate: AtomicUsize::new(usize::MAX),
+            evals: EvalCounts::default(),
+        }
+    }
+
+    /// Whether the root is proven, as a mate within `go mate` if given,
+    /// so that searching further can't change the result.
+    fn root_proven(&self) -> bool {
+        let state = self.tree[self.tree.root_node()].state();
+
+        match self.max_mate.load(Ordering::Relaxed) {
+            usize::MAX => state != GameState::Ongoing,
+            moves => matches!(state, GameState::Won(n) if usize::from(n).div_ceil(2) <= moves),
+        }
+    }
+
+    fn count_value_eval(&self) {
+        self.evals.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn count_recomputed(&self) {
+        self.evals.recomputed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Called for every node expanded or widened, which only uses the
+    /// policy network with `Priors::Network`.
+    fn count_policy_eval(&self) {
+        if self.options.priors == Priors::Network {
+            self.evals.policy.fetch_add(1, Ordering::Relaxed);
         }
     }
 
@@ -64,23 +288,26 @@ impl<'a> Searcher<'a> {
         &self,
         limits: &Limits,
         timer: &Instant,
-        #[cfg(not(feature = "uci-minimal"))] timer_last_output: &mut Instant,
+        #[cfg(not(feature = "uci-minimal"))] last_report: &mut LastReport,
         search_stats: &SearchStats,
+        sequencer: Option<&Sequencer>,
         best_move: &mut Move,
         best_move_changes: &mut i32,
         previous_score: &mut f32,
+        root_snapshot: &mut (usize, Vec<i32>),
         #[cfg(not(feature = "uci-minimal"))] uci_output: bool,
     ) {
-        if self.playout_until_full_internal(search_stats, true, || {
+        if self.playout_until_full_internal(0, search_stats, sequencer, || {
             self.check_limits(
                 limits,
                 timer,
                 #[cfg(not(feature = "uci-minimal"))]
-                timer_last_output,
+                last_report,
                 search_stats,
                 best_move,
                 best_move_changes,
                 previous_score,
+                root_snapshot,
                 #[cfg(not(feature = "uci-minimal"))]
                 uci_output,
             )
@@ -89,29 +316,81 @@ impl<'a> Searcher<'a> {
         }
     }
 
-    fn playout_until_full_worker(&self, search_stats: &SearchStats) {
-        let _ = self.playout_until_full_internal(search_stats, false, || false);
+    fn playout_until_full_worker(
+        &self,
+        thread: usize,
+        search_stats: &SearchStats,
+        sequencer: Option<&Sequencer>,
+    ) {
+        let _ = self.playout_until_full_internal(thread, search_stats, sequencer, || false);
     }
 
+    /// Thread 0 is the main thread.
     fn playout_until_full_internal<F>(
         &self,
+        thread: usize,
         search_stats: &SearchStats,
-        main_thread: bool,
+        sequencer: Option<&Sequencer>,
         mut stop: F,
     ) -> bool
     where
         F: FnMut() -> bool,
     {
+        let main_thread = thread == 0;
+
         loop {
             let mut pos = self.tree.root_position().clone();
             let mut this_depth = 0;
 
-            if iteration::perform_one(self, &mut pos, self.tree.root_node(), &mut this_depth)
+            let ticket = match sequencer {
+                Some(seq) => match seq.take_ticket(thread, self.abort) {
+                    Some(ticket) => Some((seq, ticket)),
+                    None if seq.is_stopped() => {
+                        return self.root_proven();
+                    }
+                    None => {
+                        // all other playouts below the node limit must
+                        // complete before the search is aborted
+                        if main_thread {
+                            seq.wait_until_done(self.abort);
+                        }
+
+                        return true;
+                    }
+                },
+                None => None,
+            };
+
+            let selecting = match ticket {
+                Some((seq, ticket)) => seq.begin_selection(ticket, self.abort),
+                None => true,
+            };
+
+            if !selecting
+                || iteration::perform_one(
+                    self,
+                    &mut pos,
+                    self.tree.root_node(),
+                    &mut this_depth,
+                    ticket,
+                )
                 .is_none()
             {
+                if let Some((seq, _)) = ticket {
+                    seq.stop();
+
+                    // the sequencer may have been stopped by another
+                    // thread proving the root
+                    return self.root_proven();
+                }
+
                 return false;
             }
 
+            if let Some((seq, _)) = ticket {
+                seq.end_backprop();
+            }
+
             search_stats.total_iters.fetch_add(1, Ordering::Relaxed);
             search_stats
                 .total_nodes
@@ -124,7 +403,11 @@ impl<'a> Searcher<'a> {
             }
 
             // proven checkmate
-            if self.tree[self.tree.root_node()].is_terminal() {
+            if self.root_proven() {
+                if let Some(seq) = sequencer {
+                    seq.stop();
+                }
+
                 return true;
             }
 
@@ -144,11 +427,12 @@ impl<'a> Searcher<'a> {
         &self,
         limits: &Limits,
         timer: &Instant,
-        #[cfg(not(feature = "uci-minimal"))] timer_last_output: &mut Instant,
+        #[cfg(not(feature = "uci-minimal"))] last_report: &mut LastReport,
         search_stats: &SearchStats,
         best_move: &mut Move,
         best_move_changes: &mut i32,
         previous_score: &mut f32,
+        root_snapshot: &mut (usize, Vec<i32>),
         #[cfg(not(feature = "uci-minimal"))] uci_output: bool,
     ) -> bool {
         let iters = search_stats.main_iters.load(Ordering::Relaxed);
@@ -164,10 +448,39 @@ impl<'a> Searcher<'a> {
                 }
             }
 
+            if let Some(time) = limits.opt_time {
+                if SearchHelpers::is_easy_move(self, timer, time) {
+                    return true;
+                }
+            }
+
             let (_, new_best_move, _) = self.get_best_action(self.tree.root_node());
             if new_best_move != *best_move {
                 *best_move = new_best_move;
                 *best_move_changes += 1;
+                search_stats
+                    .best_move_time
+                    .store(timer.elapsed().as_millis() as usize, Ordering::Relaxed);
+            }
+        }
+
+        // compare root visit distributions `kld_gain_interval` nodes apart,
+        // stopping once more nodes barely change them (e.g. forced moves)
+        if limits.opt_time.is_some() && self.options.min_kld_gain > 0.0 {
+            let nodes = search_stats.total_iters.load(Ordering::Relaxed);
+
+            if nodes >= root_snapshot.0 + self.options.kld_gain_interval {
+                let visits = self.root_visits();
+
+                if let Some(kld) = SearchHelpers::kl_divergence(&root_snapshot.1, &visits) {
+                    let gain = kld / (nodes - root_snapshot.0) as f32;
+
+                    if gain < self.options.min_kld_gain {
+                        return true;
+                    }
+                }
+
+                *root_snapshot = (nodes, visits);
             }
         }
 
@@ -209,28 +522,32 @@ impl<'a> Searcher<'a> {
             }
 
             #[cfg(not(feature = "uci-minimal"))]
-            if uci_output {
-                self.search_report(
+            if uci_output && self.depth_report_due(new_depth, last_report) {
+                last_report.line = Some(self.search_report(
                     new_depth,
                     search_stats.seldepth.load(Ordering::Relaxed),
                     timer,
-                    search_stats.total_nodes.load(Ordering::Relaxed),
-                );
+                    search_stats,
+                ));
 
-                *timer_last_output = Instant::now();
+                last_report.time = Instant::now();
             }
         }
 
         #[cfg(not(feature = "uci-minimal"))]
-        if uci_output && iters % 8192 == 0 && timer_last_output.elapsed().as_secs() >= 15 {
-            self.search_report(
-                search_stats.avg_depth.load(Ordering::Relaxed),
-                search_stats.seldepth.load(Ordering::Relaxed),
-                timer,
-                search_stats.total_nodes.load(Ordering::Relaxed),
-            );
+        if uci_output && iters % 8192 == 0 {
+            let depth = search_stats.avg_depth.load(Ordering::Relaxed);
 
-            *timer_last_output = Instant::now();
+            if self.periodic_report_due(depth, last_report) {
+                last_report.line = Some(self.search_report(
+                    depth,
+                    search_stats.seldepth.load(Ordering::Relaxed),
+                    timer,
+                    search_stats,
+                ));
+
+                last_report.time = Instant::now();
+            }
         }
 
         false
@@ -242,14 +559,23 @@ impl<'a> Searcher<'a> {
         limits: Limits,
         uci_output: bool,
         update_nodes: &mut usize,
-    ) -> (Move, f32) {
+    ) -> SearchResult {
         let timer = Instant::now();
         #[cfg(not(feature = "uci-minimal"))]
-        let mut timer_last_output = Instant::now();
+        let mut last_report = LastReport {
+            time: Instant::now(),
+            line: None,
+        };
 
         let pos = self.tree.root_position();
         let node = self.tree.root_node();
 
+        let reused = !self.tree.is_empty();
+
+        self.evals.value.store(0, Ordering::Relaxed);
+        self.evals.policy.store(0, Ordering::Relaxed);
+        self.evals.recomputed.store(0, Ordering::Relaxed);
+
         // the root node is added to an empty tree, **and not counted** towards the
         // total node count, in order for `go nodes 1` to give the expected result
         if self.tree.is_empty() {
@@ -258,19 +584,35 @@ impl<'a> Searcher<'a> {
             assert_eq!(node, ptr);
 
             self.tree[ptr].clear();
-            self.tree.expand_node(ptr, pos, self.params, self.policy, 1);
+            self.tree.expand_node(
+                ptr,
+                pos,
+                self.params,
+                self.options.priors,
+                self.policy,
+                1,
+                usize::MAX,
+            );
+            self.count_policy_eval();
 
             let root_eval = pos.get_value_wdl(self.value, self.params);
+            self.count_value_eval();
             self.tree[ptr].update(1.0 - root_eval);
         }
         // relabel preexisting root policies with root PST value
         else if self.tree[node].has_children() {
+            // conclusions inherited from the previous search may be stale
+            let decay = self.params.tree_reuse_decay();
+            if decay < 1.0 {
+                self.tree.decay_visits(node, decay);
+            }
+
             self.tree
-                .relabel_policy(node, pos, self.params, self.policy, 1);
+                .relabel_policy(node, pos, self.params, self.options.priors, self.policy, 1);
 
-            let first_child_ptr = { *self.tree[node].actions() };
+            let (first_child_ptr, num_actions) = self.tree[node].children();
 
-            for action in 0..self.tree[node].num_actions() {
+            for action in 0..num_actions {
                 let ptr = first_child_ptr + action;
 
                 if ptr.is_null() || !self.tree[ptr].has_children() {
@@ -279,37 +621,78 @@ impl<'a> Searcher<'a> {
 
                 let mut child = pos.clone();
                 child.make_move(self.tree[ptr].parent_move());
-                self.tree
-                    .relabel_policy(ptr, &child, self.params, self.policy, 2);
+                self.tree.relabel_policy(
+                    ptr,
+                    &child,
+                    self.params,
+                    self.options.priors,
+                    self.policy,
+                    2,
+                );
             }
         }
 
+        // nodes kept (in either half), and root visits and Q inherited
+        // from the previous search
+        let inherited = reused.then(|| {
+            let root = &self.tree[node];
+            (self.tree.subtree_size(node), root.visits(), root.q())
+        });
+
+        self.max_mate
+            .store(limits.max_mate.unwrap_or(usize::MAX), Ordering::Relaxed);
+
+        *self.gumbel.write().unwrap() = match self.options.root_selection {
+            RootSelection::Gumbel => GumbelRoot::new(self, limits.max_nodes),
+            RootSelection::Puct => None,
+        };
+
+        self.tree.corrhist().age(self.params);
+
         let search_stats = SearchStats::default();
 
         let mut best_move = Move::NULL;
         let mut best_move_changes = 0;
         let mut previous_score = f32::NEG_INFINITY;
+        let mut root_snapshot = (0, Vec::new());
+
+        let sequencer = if self.options.round_robin_smp {
+            Some(Sequencer::round_robin(threads, limits.max_nodes))
+        } else {
+            self.options
+                .repro_smp
+                .then(|| Sequencer::new(threads, limits.max_nodes))
+        };
 
         // search loop
         while !self.abort.load(Ordering::Relaxed) {
+            if let Some(seq) = &sequencer {
+                seq.restart();
+            }
+
             thread::scope(|s| {
                 s.spawn(|| {
                     self.playout_until_full_main(
                         &limits,
                         &timer,
                         #[cfg(not(feature = "uci-minimal"))]
-                        &mut timer_last_output,
+                        &mut last_report,
                         &search_stats,
+                        sequencer.as_ref(),
                         &mut best_move,
                         &mut best_move_changes,
                         &mut previous_score,
+                        &mut root_snapshot,
                         #[cfg(not(feature = "uci-minimal"))]
                         uci_output,
                     );
                 });
 
-                for _ in 0..threads - 1 {
-                    s.spawn(|| self.playout_until_full_worker(&search_stats));
+                for thread in 1..threads {
+                    let (search_stats, sequencer) = (&search_stats, sequencer.as_ref());
+                    s.spawn(move || {
+                        self.playout_until_full_worker(thread, search_stats, sequencer)
+                    });
                 }
             });
 
@@ -325,54 +708,288 @@ impl<'a> Searcher<'a> {
                 search_stats.avg_depth.load(Ordering::Relaxed).max(1),
                 search_stats.seldepth.load(Ordering::Relaxed),
                 &timer,
-                search_stats.total_nodes.load(Ordering::Relaxed),
+                &search_stats,
+            );
+            self.rates_report(&timer, &search_stats);
+
+            let (current, older) = self.tree.hash_usage();
+            log!(
+                Debug,
+                "hash",
+                "entries current {current} older {older} permille"
             );
+
+            if self.options.reuse_stats {
+                self.reuse_report(inherited, &search_stats);
+            }
         }
 
-        let (_, mov, q) = self.get_best_action(self.tree.root_node());
-        (mov, q)
+        let mut result = self.search_result();
+        result.best_move_time = search_stats.best_move_time.load(Ordering::Relaxed) as u128;
+        result.nodes = search_stats.total_nodes.load(Ordering::Relaxed);
+        result
     }
 
-    fn search_report(&self, depth: usize, seldepth: usize, timer: &Instant, nodes: usize) {
-        print!("info depth {depth} seldepth {seldepth} ");
-        let (pv_line, score) = self.get_pv(depth);
+    fn search_result(&self) -> SearchResult {
+        let root = self.tree.root_node();
+        let (best_ptr, best_move, q) = self.get_best_action(root);
+        let half = self.tree.half() > 0;
 
-        if score > 1.0 {
-            print!("score mate {} ", (pv_line.len() + 1) / 2);
-        } else if score < 0.0 {
-            print!("score mate -{} ", pv_line.len() / 2);
-        } else {
-            let cp = Searcher::get_cp(score);
-            print!("score cp {cp:.0} ");
+        let (first_child_ptr, num_actions) = self.tree[root].children();
+        let mut children = (0..num_actions)
+            .map(|action| first_child_ptr + action)
+            .filter(|&ptr| self.tree[ptr].visits() > 0)
+            .collect::<Vec<_>>();
+
+        children.sort_by(|&a, &b| {
+            let key = |ptr| Tree::best_child_key(&self.tree[ptr]);
+            key(b).total_cmp(&key(a))
+        });
+
+        // the move played comes first, even if it isn't the best by Q
+        if let Some(idx) = children.iter().position(|&ptr| ptr == best_ptr) {
+            children[..=idx].rotate_right(1);
+        }
+
+        let lines = children
+            .into_iter()
+            .map(|mut ptr| {
+                let score = Score::from_child(self.tree[ptr].state(), self.tree[ptr].q());
+                let mut line = Vec::new();
+
+                while !ptr.is_null() && ptr.half() == half {
+                    let node = &self.tree[ptr];
+                    line.push((node.parent_move(), node.q(), node.visits()));
+
+                    let idx = self.tree.get_best_child(ptr);
+
+                    if idx == usize::MAX {
+                        break;
+                    }
+
+                    ptr = node.actions() + idx;
+                }
+
+                (score, line)
+            })
+            .collect();
+
+        SearchResult::new(best_move, q, self.tree.root_position().clone(), lines)
+    }
+
+    fn reuse_report(&self, inherited: Option<(usize, i32, f32)>, search_stats: &SearchStats) {
+        let playouts = search_stats.total_iters.load(Ordering::Relaxed);
+
+        let recomputed = self.evals.recomputed.load(Ordering::Relaxed);
+
+        let Some((nodes, visits, q)) = inherited else {
+            println!("info string tree reuse none (new tree) playouts {playouts}");
+            return;
+        };
+
+        // root Q is stored from the perspective of its (nonexistent) parent
+        let cp_100_q = self.options.cp_100_q;
+        let start = Score::Q(1.0 - q).cp_with(cp_100_q);
+        let end = Score::Q(1.0 - self.tree[self.tree.root_node()].q()).cp_with(cp_100_q);
+        let drift = end - start;
+
+        println!(
+            "info string tree reuse tree nodes {nodes} inherited visits {visits} recomputed nodes {recomputed} playouts {playouts} root cp {start:.0} -> {end:.0} drift {drift:.0}"
+        );
+    }
+
+    /// Whether to report reaching a new depth: always, unless it is less
+    /// than `report_interval` since the last report and the score hasn't
+    /// changed by much.
+    #[cfg(not(feature = "uci-minimal"))]
+    fn depth_report_due(&self, depth: usize, last_report: &LastReport) -> bool {
+        const LARGE_SCORE_CHANGE: f32 = 50.0;
+
+        let interval = u128::from(self.options.report_interval);
+
+        if interval == 0 || last_report.time.elapsed().as_millis() >= interval {
+            return true;
+        }
+
+        let Some((_, last_score)) = &last_report.line else {
+            return true;
+        };
+
+        match (self.get_pv(depth).1, *last_score) {
+            (Score::Q(q), Score::Q(last_q)) => {
+                (Score::Q(q).cp() - Score::Q(last_q).cp()).abs() >= LARGE_SCORE_CHANGE
+            }
+            (score, last_score) => score != last_score,
+        }
+    }
+
+    /// Whether to report between depths: every 15 seconds, and with a
+    /// `report_interval` set, whenever it has passed and the PV or score
+    /// has changed.
+    #[cfg(not(feature = "uci-minimal"))]
+    fn periodic_report_due(&self, depth: usize, last_report: &LastReport) -> bool {
+        let elapsed = last_report.time.elapsed().as_millis();
+
+        if elapsed >= 15000 {
+            return true;
         }
 
+        let interval = u128::from(self.options.report_interval);
+
+        interval > 0
+            && elapsed >= interval
+            && last_report.line.as_ref() != Some(&self.get_pv(depth))
+    }
+
+    /// Print an `info` line, returning the PV and score in it.
+    fn search_report(
+        &self,
+        depth: usize,
+        seldepth: usize,
+        timer: &Instant,
+        search_stats: &SearchStats,
+    ) -> (Vec<Move>, Score) {
+        let (pv_line, score) = self.get_pv(depth);
+
         let elapsed = timer.elapsed();
-        let nps = nodes as f32 / elapsed.as_secs_f32();
+        let per_sec = |count: usize| count as f32 / elapsed.as_secs_f32();
         let ms = elapsed.as_millis();
 
-        print!("time {ms} nodes {nodes} nps {nps:.0} pv");
+        let nodes = search_stats.total_nodes.load(Ordering::Relaxed);
+        let playouts = search_stats.total_iters.load(Ordering::Relaxed);
+        let value_evals = self.evals.value.load(Ordering::Relaxed);
+        let policy_evals = self.evals.policy.load(Ordering::Relaxed);
+        let nps = per_sec(nodes);
+
+        let hashfull = self.tree.hashfull();
+
+        let mut pos = self.tree.root_position().clone();
+        let pv = pv_line
+            .iter()
+            .map(|&mov| {
+                let notation = if self.options.san_output {
+                    pos.conv_mov_to_san(mov)
+                } else {
+                    pos.conv_mov_to_str(mov)
+                };
 
-        for mov in pv_line {
-            print!(" {}", self.tree.root_position().conv_mov_to_str(mov));
+                pos.make_move(mov);
+                notation
+            })
+            .collect::<Vec<_>>();
+
+        if self.options.output_format == OutputFormat::Json {
+            println!(
+                "{{\"info\":{{\"depth\":{depth},\"seldepth\":{seldepth},\"score\":{},\
+                 \"time\":{ms},\"nodes\":{nodes},\"nps\":{nps:.0},\"playouts\":{playouts},\
+                 \"pps\":{:.0},\"value_evals\":{value_evals},\"value_eps\":{:.0},\
+                 \"policy_evals\":{policy_evals},\"policy_eps\":{:.0},\"hashfull\":{hashfull},\
+                 \"pv\":{},\"multipv\":{},\"moves\":{}}}}}",
+                score.to_json(self.options.cp_100_q),
+                per_sec(playouts),
+                per_sec(value_evals),
+                per_sec(policy_evals),
+                json_strings(&pv),
+                self.json_variations(),
+                self.json_root_moves(),
+            );
+        } else {
+            println!(
+                "info depth {depth} seldepth {seldepth} score {} time {ms} nodes {nodes} \
+                 nps {nps:.0} hashfull {hashfull} pv{}",
+                score.to_uci(self.options.cp_100_q),
+                pv.iter().map(|mov| format!(" {mov}")).collect::<String>()
+            );
+        }
+
+        (pv_line, score)
+    }
+
+    /// Print the playout and evaluation rates once the search has finished,
+    /// as an `info string` so that GUIs parsing `nps` are unaffected (JSON
+    /// lines carry them already).
+    fn rates_report(&self, timer: &Instant, search_stats: &SearchStats) {
+        if self.options.output_format == OutputFormat::Json {
+            return;
         }
 
-        println!();
+        let per_sec = |count: usize| count as f32 / timer.elapsed().as_secs_f32();
+
+        let playouts = search_stats.total_iters.load(Ordering::Relaxed);
+        let value_evals = self.evals.value.load(Ordering::Relaxed);
+        let policy_evals = self.evals.policy.load(Ordering::Relaxed);
+
+        println!(
+            "info string playouts {playouts} pps {:.0} value evals {value_evals} \
+             eps {:.0} policy evals {policy_evals} eps {:.0}",
+            per_sec(playouts),
+            per_sec(value_evals),
+            per_sec(policy_evals),
+        );
+    }
+
+    /// The best line of each visited root move, best first.
+    fn json_variations(&self) -> String {
+        let lines = self
+            .search_result()
+            .variations(usize::MAX)
+            .into_iter()
+            .map(|variation| {
+                let pv = variation
+                    .moves
+                    .into_iter()
+                    .map(|mov| {
+                        if self.options.san_output {
+                            mov.san
+                        } else {
+                            mov.uci
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                format!(
+                    "{{\"score\":{},\"pv\":{}}}",
+                    variation.score.to_json(self.options.cp_100_q),
+                    json_strings(&pv)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!("[{}]", lines.join(","))
+    }
+
+    /// Visits, Q and policy of every root move.
+    fn json_root_moves(&self) -> String {
+        let root = self.tree.root_node();
+        let pos = self.tree.root_position();
+        let (first_child_ptr, num_actions) = self.tree[root].children();
+
+        let moves = (0..num_actions)
+            .map(|action| {
+                let child = &self.tree[first_child_ptr + action];
+
+                format!(
+                    "{{\"move\":\"{}\",\"visits\":{},\"q\":{:.4},\"policy\":{:.4}}}",
+                    pos.conv_mov_to_str(child.parent_move()),
+                    child.visits(),
+                    child.q(),
+                    child.policy(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!("[{}]", moves.join(","))
     }
 
-    fn get_pv(&self, mut depth: usize) -> (Vec<Move>, f32) {
+    fn get_pv(&self, mut depth: usize) -> (Vec<Move>, Score) {
         let mate = self.tree[self.tree.root_node()].is_terminal();
 
         let (mut ptr, mut mov, q) = self.get_best_action(self.tree.root_node());
 
         let score = if !ptr.is_null() {
-            match self.tree[ptr].state() {
-                GameState::Lost(_) => 1.1,
-                GameState::Won(_) => -0.1,
-                GameState::Draw => 0.5,
-                GameState::Ongoing => q,
-            }
+            Score::from_child(self.tree[ptr].state(), q)
         } else {
-            q
+            Score::Q(q)
         };
 
         let mut pv = Vec::new();
@@ -394,27 +1011,33 @@ impl<'a> Searcher<'a> {
     }
 
     fn get_best_action(&self, node: NodePtr) -> (NodePtr, Move, f32) {
-        let idx = self.tree.get_best_child(node);
-        let ptr = *self.tree[node].actions() + idx;
+        let idx = self.best_child(node);
+        let ptr = self.tree[node].actions() + idx;
         let child = &self.tree[ptr];
         (ptr, child.parent_move(), child.q())
     }
 
-    fn get_cp(score: f32) -> f32 {
-        let clamped_score = score.clamp(0.0, 1.0);
-        let deviation = (clamped_score - 0.5).abs();
-        let sign = (clamped_score - 0.5).signum();
-        if deviation > 0.107 {
-            (100.0 + 2923.0 * (deviation - 0.107)) * sign
-        } else {
-            let adjusted_score = 0.5 + (clamped_score - 0.5).powi(3) * 100.0;
-            -200.0 * (1.0 / adjusted_score - 1.0).ln()
+    fn best_child(&self, node: NodePtr) -> usize {
+        if node == self.tree.root_node() {
+            if let Some(gumbel) = &*self.gumbel.read().unwrap() {
+                return gumbel.best_action(self, &self.tree[node]);
+            }
         }
+
+        self.tree.get_best_child(node)
+    }
+
+    fn root_visits(&self) -> Vec<i32> {
+        let (first_child_ptr, num_actions) = self.tree[self.tree.root_node()].children();
+
+        (0..num_actions)
+            .map(|action| self.tree[first_child_ptr + action].visits())
+            .collect()
     }
 
     pub fn display_moves(&self) {
-        let first_child_ptr = { *self.tree[self.tree.root_node()].actions() };
-        for action in 0..self.tree[self.tree.root_node()].num_actions() {
+        let (first_child_ptr, num_actions) = self.tree[self.tree.root_node()].children();
+        for action in 0..num_actions {
             let child = &self.tree[first_child_ptr + action];
             let mov = self
                 .tree
@@ -429,3 +1052,12 @@ impl<'a> Searcher<'a> {
         }
     }
 }
+
+fn json_strings(strings: &[String]) -> String {
+    let quoted = strings
+        .iter()
+        .map(|s| format!("\"{s}\""))
+        .collect::<Vec<_>>();
+
+    format!("[{}]", quoted.join(","))
+}
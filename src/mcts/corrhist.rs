@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::{
+    chess::{
+        consts::{Piece, Side},
+        ChessState,
+    },
+    mcts::MctsParams,
+};
+
+/// Correction history: how far search results have ended up from the value
+/// network's evaluation of positions, averaged over positions that share a
+/// key, and added to later evaluations of positions with that key.
+///
+/// There are two tables, blended by separate weights: one keyed by a
+/// material signature (piece counts of each side, and the square colours
+/// of their bishops), so that systematic errors in particular material
+/// configurations, e.g. opposite-coloured bishops, are corrected, and one
+/// keyed by the pawn structure.
+///
+/// Entries lose weight by `corrhist_decay` for each move played, so that
+/// corrections from earlier phases of the game are soon outweighed.
+pub struct CorrHist {
+    material: Box<[CorrHistEntry]>,
+    pawns: Box<[CorrHistEntry]>,
+    /// Changes of root since the entries were last aged.
+    new_roots: AtomicU32,
+}
+
+impl Default for CorrHist {
+    fn default() -> Self {
+        let table = || (0..Self::SIZE).map(|_| CorrHistEntry::default()).collect();
+
+        Self {
+            material: table(),
+            pawns: table(),
+            new_roots: AtomicU32::new(0),
+        }
+    }
+}
+
+impl CorrHist {
+    const SIZE: usize = 16384;
+
+    pub fn is_enabled(params: &MctsParams) -> bool {
+        params.corrhist_material_weight() > 0.0 || params.corrhist_pawn_weight() > 0.0
+    }
+
+    pub fn clear(&self) {
+        for entry in self.material.iter().chain(self.pawns.iter()) {
+            entry.clear();
+        }
+    }
+
+    /// Called when the tree is moved to a different root position.
+    pub fn new_root(&self) {
+        self.new_roots.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decay the weight of every entry once for each root change since the
+    /// last call, at the start of a search.
+    pub fn age(&self, params: &MctsParams) {
+        let new_roots = self.new_roots.swap(0, Ordering::Relaxed);
+        let decay = params.corrhist_decay();
+
+        if new_roots == 0 || decay >= 1.0 || !Self::is_enabled(params) {
+            return;
+        }
+
+        let factor = decay.powi(new_roots.min(64) as i32);
+
+        for entry in self.material.iter().chain(self.pawns.iter()) {
+            entry.scale(factor);
+        }
+    }
+
+    /// Amount to add to the value network's Q for the side to move.
+    pub fn correction(&self, pos: &ChessState, params: &MctsParams) -> f32 {
+        let (material, pawns) = self.entries(pos);
+
+        params.corrhist_material_weight() * material.average()
+            + params.corrhist_pawn_weight() * pawns.average()
+    }
+
+    /// Start loading the entries of `pos` into cache.
+    pub fn prefetch(&self, pos: &ChessState) {
+        let (material, pawns) = self.entries(pos);
+        crate::prefetch(material);
+        crate::prefetch(pawns);
+    }
+
+    /// Record that the search found a Q of `raw + delta` for the side to
+    /// move, where the network gave `raw`.
+    pub fn update(&self, pos: &ChessState, delta: f32) {
+        let (material, pawns) = self.entries(pos);
+        material.update(delta);
+        pawns.update(delta);
+    }
+
+    fn entries(&self, pos: &ChessState) -> (&CorrHistEntry, &CorrHistEntry) {
+        (
+            &self.material[index(material_key(pos))],
+            &self.pawns[index(pos.board().pawn_hash())],
+        )
+    }
+}
+
+/// Running average of the deltas recorded at an entry, as the sum of the
+/// deltas and of their weights, both `f32` packed into one atomic so that
+/// they are read and written together.
+#[derive(Default)]
+pub struct CorrHistEntry(AtomicU64);
+
+impl CorrHistEntry {
+    /// Past this, older deltas are gradually forgotten.
+    const MAX_WEIGHT: f32 = 256.0;
+
+    fn get(&self) -> (f32, f32) {
+        let bits = self.0.load(Ordering::Relaxed);
+        (
+            f32::from_bits((bits >> 32) as u32),
+            f32::from_bits(bits as u32),
+        )
+    }
+
+    fn set(&self, delta_sum: f32, weight_sum: f32) {
+        let bits = (u64::from(delta_sum.to_bits()) << 32) | u64::from(weight_sum.to_bits());
+        self.0.store(bits, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.0.store(0, Ordering::Relaxed);
+    }
+
+    /// Scale the weight of the deltas, keeping their average.
+    fn scale(&self, factor: f32) {
+        let (delta_sum, weight_sum) = self.get();
+
+        if weight_sum > 0.0 {
+            self.set(delta_sum * factor, weight_sum * factor);
+        }
+    }
+
+    pub fn average(&self) -> f32 {
+        let (delta_sum, weight_sum) = self.get();
+
+        if weight_sum > 0.0 {
+            delta_sum / weight_sum
+        } else {
+            0.0
+        }
+    }
+
+    /// Concurrent updates may occasionally overwrite each other, which
+    /// only loses a sample.
+    fn update(&self, delta: f32) {
+        let (mut delta_sum, mut weight_sum) = self.get();
+
+        if weight_sum >= Self::MAX_WEIGHT {
+            let scale = (Self::MAX_WEIGHT - 1.0) / weight_sum;
+            delta_sum *= scale;
+            weight_sum *= scale;
+        }
+
+        self.set(delta_sum + delta, weight_sum + 1.0);
+    }
+}
+
+/// Piece counts of both sides, whether they have bishops on light and
+/// dark squares, and the side to move (as deltas are relative to it).
+fn material_key(pos: &ChessState) -> u64 {
+    const LIGHT_SQUARES: u64 = 0x55AA_55AA_55AA_55AA;
+
+    let board = pos.board();
+    let mut key = pos.stm() as u64;
+
+    for side in [Side::WHITE, Side::BLACK] {
+        let ours = board.piece(side);
+
+        for piece in Piece::PAWN..Piece::KING {
+            let count = u64::from((ours & board.piece(piece)).count_ones().min(15));
+            key = (key << 4) | count;
+        }
+
+        let bishops = ours & board.piece(Piece::BISHOP);
+        key = (key << 2)
+            | u64::from(bishops & LIGHT_SQUARES != 0) << 1
+            | u64::from(bishops & !LIGHT_SQUARES != 0);
+    }
+
+    key
+}
+
+fn index(key: u64) -> usize {
+    let hash = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    (hash >> 32) as usize % CorrHist::SIZE
+}
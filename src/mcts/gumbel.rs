@@ -0,0 +1,162 @@
+use crate::{
+    chess::GameState,
+    mcts::{SearchHelpers, Searcher},
+    rng::Rng,
+    tree::Node,
+};
+
+/// Root move selection by Sequential Halving with Gumbel noise, as in
+/// Gumbel AlphaZero (Danihelka et al., 2022), for `RootSelection::Gumbel`.
+///
+/// The `gumbel_considered` root moves with the highest `g + log(prior)`,
+/// where `g` is Gumbel noise, share the node budget over `log2(m)` phases,
+/// each visiting the remaining moves equally often before halving them by
+/// `g + log(prior) + sigma(q)`. The move played is the best survivor by
+/// the same score.
+///
+/// Moves are re-ranked as the search goes, rather than only at the end of
+/// each phase, so that no state has to be shared between threads.
+pub struct GumbelRoot {
+    /// Considered root actions and their perturbed logits, best first.
+    actions: Vec<(usize, f32)>,
+    /// Total root visits of the search, or `None` without a node limit, in
+    /// which case the budget doubles whenever it is used up.
+    budget: Option<usize>,
+    /// Root visits before the search started.
+    base_visits: i32,
+}
+
+impl GumbelRoot {
+    /// `None` if the root has no moves.
+    pub fn new(searcher: &Searcher, max_nodes: usize) -> Option<Self> {
+        let tree = searcher.tree;
+        let root = &tree[tree.root_node()];
+        let (first_child_ptr, num_actions) = root.children();
+
+        // noise is seeded from the position when searches must be
+        // reproducible, and from the OS otherwise
+        let options = searcher.options;
+        let mut rng = if options.repro_smp || options.round_robin_smp {
+            Rng::with_seed(tree.root_position().hash())
+        } else {
+            Rng::new()
+        };
+
+        let mut actions = (0..num_actions)
+            .map(|action| {
+                let policy = tree[first_child_ptr + action].policy();
+                (action, gumbel(&mut rng) + policy.max(1e-6).ln())
+            })
+            .collect::<Vec<_>>();
+
+        actions.sort_by(|a, b| b.1.total_cmp(&a.1));
+        actions.truncate(searcher.params.gumbel_considered().max(1) as usize);
+
+        (!actions.is_empty()).then(|| Self {
+            actions,
+            budget: (max_nodes < i32::MAX as usize).then_some(max_nodes),
+            base_visits: root.visits(),
+        })
+    }
+
+    /// The least visited of the moves still being considered.
+    pub fn pick_action(&self, searcher: &Searcher, root: &Node) -> usize {
+        let survivors = self.survivors(searcher, root);
+        let visits = |action: usize| {
+            let child = &searcher.tree[root.actions() + action];
+            child.visits() + i32::from(child.threads())
+        };
+
+        let mut best = survivors[0];
+
+        for &action in &survivors[1..] {
+            if visits(action) < visits(best) {
+                best = action;
+            }
+        }
+
+        best
+    }
+
+    /// The move to play, unless a move is proven to win.
+    pub fn best_action(&self, searcher: &Searcher, root: &Node) -> usize {
+        let best = searcher.tree.get_best_child(searcher.tree.root_node());
+
+        if best == usize::MAX
+            || matches!(
+                searcher.tree[root.actions() + best].state(),
+                GameState::Lost(_)
+            )
+        {
+            return best;
+        }
+
+        self.survivors(searcher, root)
+            .into_iter()
+            .find(|&action| searcher.tree[root.actions() + action].visits() > 0)
+            .unwrap_or(best)
+    }
+
+    /// Moves considered in the current phase, best first.
+    fn survivors(&self, searcher: &Searcher, root: &Node) -> Vec<usize> {
+        let first_child_ptr = root.actions();
+        let visited_policy = searcher.tree.visited_policy(searcher.tree.root_node());
+        let fpu = SearchHelpers::get_fpu(searcher.params, root, visited_policy);
+
+        let max_visits = self
+            .actions
+            .iter()
+            .map(|&(action, _)| searcher.tree[first_child_ptr + action].visits())
+            .max()
+            .unwrap_or(0);
+
+        let scale = (searcher.params.gumbel_c_visit() + max_visits as f32)
+            * searcher.params.gumbel_c_scale();
+
+        let mut scored = self
+            .actions
+            .iter()
+            .map(|&(action, logit)| {
+                let child = &searcher.tree[first_child_ptr + action];
+                let q = SearchHelpers::get_action_value(child, fpu, searcher.options.risk);
+                (action, logit + scale * q)
+            })
+            .collect::<Vec<_>>();
+
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let playouts = (root.visits() - self.base_visits).max(0) as usize;
+        scored.truncate(self.phase_width(playouts));
+        scored.into_iter().map(|(action, _)| action).collect()
+    }
+
+    /// Number of moves still considered after `playouts` of the budget.
+    fn phase_width(&self, playouts: usize) -> usize {
+        let considered = self.actions.len();
+        let phases = considered.next_power_of_two().trailing_zeros().max(1) as usize;
+
+        let budget = self
+            .budget
+            .unwrap_or_else(|| (playouts + 1).next_power_of_two().max(considered * phases));
+
+        let mut width = considered;
+        let mut spent = 0;
+
+        for _ in 0..phases {
+            spent += (budget / (phases * width)).max(1) * width;
+
+            if playouts < spent {
+                return width;
+            }
+
+            width = (width / 2).max(1);
+        }
+
+        width
+    }
+}
+
+/// Standard Gumbel noise.
+fn gumbel(rng: &mut Rng) -> f32 {
+    -(-rng.next_f32().ln()).ln()
+}
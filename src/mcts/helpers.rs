@@ -58,6 +58,16 @@ impl SearchHelpers {
         Self::base_explore_scaling(params, node)
     }
 
+    /// Progressive Widening
+    ///
+    /// Number of children a node (outside the root) should have by now.
+    pub fn get_widening_width(params: &MctsParams, node: &Node) -> usize {
+        let visits = node.visits().max(1) as f32;
+        let width = params.widening_base()
+            + params.widening_scale() * visits.powf(params.widening_exponent());
+        width.ceil() as usize
+    }
+
     /// Common depth PST
     pub fn get_pst(depth: usize, q: f32, params: &MctsParams) -> f32 {
         let scalar = q - q.min(params.winning_pst_threshold());
@@ -67,49 +77,95 @@ impl SearchHelpers {
         base_pst + (params.winning_pst_max() - base_pst) * t
     }
 
+    /// KL divergence of the root visit distribution `new` from the earlier
+    /// `old`, or `None` if they can't be compared (e.g. the root changed).
+    pub fn kl_divergence(old: &[i32], new: &[i32]) -> Option<f32> {
+        let old_total = old.iter().map(|&v| i64::from(v)).sum::<i64>();
+        let new_total = new.iter().map(|&v| i64::from(v)).sum::<i64>();
+
+        if old.len() != new.len() || old_total == 0 || new_total == 0 {
+            return None;
+        }
+
+        let mut kld = 0.0;
+
+        for (&o, &n) in old.iter().zip(new) {
+            if o == 0 {
+                continue;
+            }
+
+            // visits can only shrink if the root was replaced
+            if n == 0 {
+                return None;
+            }
+
+            let p = o as f32 / old_total as f32;
+            let q = n as f32 / new_total as f32;
+            kld += p * (p / q).ln();
+        }
+
+        Some(kld)
+    }
+
     /// First Play Urgency
     ///
+    /// The parent's value, lowered by `fpu_base` and by `fpu_reduction`
+    /// times the total prior of the children already visited.
+    ///
     /// #### Note
     /// Must return a value in [0, 1].
-    pub fn get_fpu(node: &Node) -> f32 {
-        1.0 - node.q()
+    pub fn get_fpu(params: &MctsParams, node: &Node, visited_policy: f32) -> f32 {
+        let reduction = params.fpu_base() + params.fpu_reduction() * visited_policy;
+        (1.0 - node.q() - reduction).clamp(0.0, 1.0)
     }
 
     /// Get a predicted win probability for an action
     ///
+    /// Shifted by `risk` standard deviations of its Q (see
+    /// `SearchOptions::risk`).
+    ///
     /// #### Note
     /// Must return a value in [0, 1].
-    pub fn get_action_value(node: &Node, fpu: f32) -> f32 {
+    pub fn get_action_value(node: &Node, fpu: f32, risk: f32) -> f32 {
         if node.visits() == 0 {
             fpu
-        } else {
+        } else if risk == 0.0 {
             node.q()
+        } else {
+            (node.q() + risk * node.var().sqrt()).clamp(0.0, 1.0)
         }
     }
 
-    /// Calculates the maximum allowed time usage for a search
+    /// Calculates the optimum and maximum time usage for a search
     ///
     /// #### Note
-    /// This will be overriden by a `go movetime` command,
-    /// and a move overhead will be applied to this, so no
-    /// need for it here.
+    /// `move_overhead` is taken off both for the latency of this
+    /// move, and reserved for each of the future moves budgeted for.
     pub fn get_time(
         time: u64,
         increment: Option<u64>,
         ply: u32,
         movestogo: Option<u64>,
+        move_overhead: u64,
         params: &MctsParams,
     ) -> (u128, u128) {
+        let overhead = u128::from(move_overhead);
+
         if let Some(mtg) = movestogo {
             // Cyclic time control (x moves in y seconds)
-            let max_time = (time as f64 / (mtg as f64).clamp(1.0, 30.0)) as u128;
+            let mtg = mtg.clamp(1, 30);
+            let time_left = time.saturating_sub(move_overhead * (mtg - 1));
+            let max_time = (time_left / mtg) as u128;
+            let max_time = max_time.saturating_sub(overhead);
             (max_time, max_time)
         } else {
             // Increment time control (x seconds + y increment)
             let inc = increment.unwrap_or(0);
             let mtg = params.tm_mtg() as u64;
 
-            let time_left = (time + inc * (mtg - 1) - 10 * (2 + mtg)).max(1) as f64;
+            let time_left = (time + inc * (mtg - 1))
+                .saturating_sub(move_overhead * (mtg - 1))
+                .max(1) as f64;
             let log_time = (time_left / 1000.0).log10();
 
             let opt_constant = (params.tm_opt_value1() / 100.0
@@ -137,7 +193,10 @@ impl SearchHelpers {
             let max_time =
                 (max_scale * opt_time as f64).min(time as f64 * params.tm_max_time()) as u128;
 
-            (opt_time, max_time)
+            (
+                opt_time.saturating_sub(overhead),
+                max_time.saturating_sub(overhead),
+            )
         }
     }
 
@@ -152,8 +211,7 @@ impl SearchHelpers {
         let elapsed = timer.elapsed().as_millis();
 
         // Use more time if our eval is falling, and vice versa
-        let (_, mut score) = searcher.get_pv(0);
-        score = Searcher::get_cp(score);
+        let score = searcher.get_pv(0).1.cp();
         let eval_diff = if previous_score == f32::NEG_INFINITY {
             0.0
         } else {
@@ -182,4 +240,29 @@ impl SearchHelpers {
 
         (elapsed >= total_time, score)
     }
+
+    /// Easy Move
+    ///
+    /// Whether the best move is clear enough to be played after only a
+    /// fraction of the soft time limit: it is the only legal one, or holds
+    /// an overwhelming share of both visits and policy.
+    pub fn is_easy_move(searcher: &Searcher, timer: &Instant, time: u128) -> bool {
+        let root = &searcher.tree[searcher.tree.root_node()];
+
+        if root.num_actions() == 1 {
+            return true;
+        }
+
+        let elapsed = timer.elapsed().as_millis() as f32;
+        if elapsed < time as f32 * searcher.params.tm_easy_time() {
+            return false;
+        }
+
+        let (best_child_ptr, _, _) = searcher.get_best_action(searcher.tree.root_node());
+        let best = &searcher.tree[best_child_ptr];
+        let visit_share = best.visits() as f32 / root.visits().max(1) as f32;
+
+        visit_share >= searcher.params.tm_easy_visits()
+            && best.policy() >= searcher.params.tm_easy_policy()
+    }
 }
@@ -3,13 +3,16 @@ use crate::{
     tree::{Node, NodePtr},
 };
 
-use super::{SearchHelpers, Searcher};
+use super::{CorrHist, SearchHelpers, Searcher, Sequencer};
 
+/// Performs a playout from `ptr`, with `ticket` set if playouts are
+/// being sequenced (`ReproSMP`).
 pub fn perform_one(
     searcher: &Searcher,
     pos: &mut ChessState,
     ptr: NodePtr,
     depth: &mut usize,
+    ticket: Option<(&Sequencer, usize)>,
 ) -> Option<f32> {
     *depth += 1;
 
@@ -17,53 +20,143 @@ pub fn perform_one(
     let tree = searcher.tree;
     let node = &tree[ptr];
 
-    let mut u = if node.is_terminal() || node.visits() == 0 {
+    // nodes at the maximum depth are never expanded, only evaluated
+    let at_max_depth = searcher
+        .options
+        .max_tree_depth
+        .is_some_and(|max| *depth > max);
+
+    // visits to give a new node for a transposition's Q
+    let mut seed_visits = None;
+
+    // the position and its raw evaluation, to compare with the result of
+    // searching it for correction history
+    let mut corrhist_sample = None;
+
+    // a proven root is searched on for a shorter mate with `go mate`
+    let terminal = node.is_terminal()
+        && !(ptr == tree.root_node() && node.has_children() && !searcher.root_proven());
+
+    let mut u = if terminal || node.visits() == 0 || at_max_depth {
         if node.visits() == 0 {
             node.set_state(pos.game_state());
         }
 
         // probe hash table to use in place of network
-        if node.state() == GameState::Ongoing {
-            if let Some(entry) = tree.probe_hash(hash) {
+        let entry = if node.state() == GameState::Ongoing {
+            tree.probe_hash(hash)
+        } else {
+            None
+        };
+
+        // selection is over, evaluation can run alongside other playouts
+        if let Some((seq, _)) = ticket {
+            seq.end_selection();
+        }
+
+        let u = match entry {
+            Some(entry) => {
+                if node.visits() == 0 {
+                    let max = searcher.params.transposition_seed_visits();
+                    seed_visits = Some(entry.visits().min(max));
+
+                    if tree.is_older_hash_entry(entry) {
+                        searcher.count_recomputed();
+                    }
+                }
+
                 entry.q()
-            } else {
-                get_utility(searcher, ptr, pos)
             }
-        } else {
-            get_utility(searcher, ptr, pos)
+            None => get_utility(searcher, ptr, pos),
+        };
+
+        if let Some((seq, ticket)) = ticket {
+            if !seq.begin_backprop(ticket, searcher.abort) {
+                return None;
+            }
         }
+
+        u
     } else {
+        // the root always has all its moves
+        let width = if searcher.options.progressive_widening && ptr != tree.root_node() {
+            SearchHelpers::get_widening_width(searcher.params, node)
+        } else {
+            usize::MAX
+        };
+
         // expand node on the second visit
         if node.is_not_expanded() {
-            tree.expand_node(ptr, pos, searcher.params, searcher.policy, *depth)?;
+            tree.expand_node(
+                ptr,
+                pos,
+                searcher.params,
+                searcher.options.priors,
+                searcher.policy,
+                *depth,
+                width,
+            )?;
+            searcher.count_policy_eval();
         }
 
         // this node has now been accessed so we need to move its
         // children across if they are in the other tree half
         tree.fetch_children(ptr)?;
 
+        if node.can_widen() && width > node.num_actions() {
+            tree.widen_node(
+                ptr,
+                pos,
+                searcher.params,
+                searcher.options.priors,
+                searcher.policy,
+                *depth,
+                width,
+            )?;
+            searcher.count_policy_eval();
+        }
+
+        if CorrHist::is_enabled(searcher.params)
+            && node.visits() + 1 == searcher.params.corrhist_update_visits()
+        {
+            let raw = pos.get_value_wdl(searcher.value, searcher.params);
+            searcher.count_value_eval();
+            corrhist_sample = Some((pos.clone(), raw));
+        }
+
         // select action to take via PUCT
         let action = pick_action(searcher, ptr, node);
 
-        let first_child_ptr = { *node.actions() };
+        let first_child_ptr = node.actions();
         let child_ptr = first_child_ptr + action;
 
         let mov = tree[child_ptr].parent_move();
 
         pos.make_move(mov);
 
+        // the child will be evaluated, so start loading what that reads
+        // while the rest of selection is done
+        if tree[child_ptr].visits() == 0 {
+            tree.prefetch_hash(pos.hash());
+
+            if CorrHist::is_enabled(searcher.params) {
+                tree.corrhist().prefetch(pos);
+            }
+        }
+
         tree[child_ptr].inc_threads();
 
         // acquire lock to avoid issues with desynced setting of
-        // game state between threads when threads > 1
-        let lock = if tree[child_ptr].visits() == 0 {
+        // game state between threads when threads > 1 (not needed
+        // if playouts are sequenced, and would deadlock the sequencer)
+        let lock = if tree[child_ptr].visits() == 0 && ticket.is_none() {
             Some(node.actions_mut())
         } else {
             None
         };
 
         // descend further
-        let maybe_u = perform_one(searcher, pos, child_ptr, depth);
+        let maybe_u = perform_one(searcher, pos, child_ptr, depth, ticket);
 
         drop(lock);
 
@@ -82,15 +175,42 @@ pub fn perform_one(
     u = 1.0 - u;
 
     let new_q = node.update(u);
-    tree.push_hash(hash, 1.0 - new_q);
+
+    if let Some((pos, raw)) = corrhist_sample {
+        tree.corrhist().update(&pos, (1.0 - new_q) - raw);
+    }
+
+    if let Some(visits) = seed_visits {
+        node.seed_visits(visits);
+    }
+
+    tree.push_hash(hash, 1.0 - new_q, node.visits());
 
     Some(u)
 }
 
 fn get_utility(searcher: &Searcher, ptr: NodePtr, pos: &ChessState) -> f32 {
+    // contempt is from the perspective of the side to move at the root
+    let contempt = if pos.stm() == searcher.tree.root_position().stm() {
+        searcher.options.contempt
+    } else {
+        -searcher.options.contempt
+    };
+
     match searcher.tree[ptr].state() {
-        GameState::Ongoing => pos.get_value_wdl(searcher.value, searcher.params),
-        GameState::Draw => 0.5,
+        GameState::Ongoing => {
+            let mut q = pos.get_value_wdl(searcher.value, searcher.params);
+            searcher.count_value_eval();
+
+            if CorrHist::is_enabled(searcher.params) {
+                q = (q + searcher.tree.corrhist().correction(pos, searcher.params)).clamp(0.0, 1.0);
+            }
+
+            // shift drawish evaluations by up to the full contempt
+            let drawishness = 1.0 - (2.0 * q - 1.0).powi(2);
+            (q - contempt * drawishness).clamp(0.0, 1.0)
+        }
+        GameState::Draw => 0.5 - contempt,
         GameState::Lost(_) => 0.0,
         GameState::Won(_) => 1.0,
     }
@@ -99,14 +219,26 @@ fn get_utility(searcher: &Searcher, ptr: NodePtr, pos: &ChessState) -> f32 {
 fn pick_action(searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize {
     let is_root = ptr == searcher.tree.root_node();
 
+    if is_root {
+        if let Some(gumbel) = &*searcher.gumbel.read().unwrap() {
+            return gumbel.pick_action(searcher, node);
+        }
+    }
+
     let cpuct = SearchHelpers::get_cpuct(searcher.params, node, is_root);
-    let fpu = SearchHelpers::get_fpu(node);
+    let visited_policy = if searcher.params.fpu_reduction() > 0.0 {
+        searcher.tree.visited_policy(ptr)
+    } else {
+        0.0
+    };
+
+    let fpu = SearchHelpers::get_fpu(searcher.params, node, visited_policy);
     let expl_scale = SearchHelpers::get_explore_scaling(searcher.params, node);
 
     let expl = cpuct * expl_scale;
 
     searcher.tree.get_best_child_by_key(ptr, |child| {
-        let mut q = SearchHelpers::get_action_value(child, fpu);
+        let mut q = SearchHelpers::get_action_value(child, fpu, searcher.options.risk);
 
         // virtual loss
         let threads = f64::from(child.threads());
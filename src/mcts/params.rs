@@ -1,3 +1,15 @@
+/// A param in the units of `setoption` (floats scaled by 1000), for tuning
+/// with SPSA.
+#[derive(Clone, Copy, Debug)]
+pub struct Tunable {
+    pub name: &'static str,
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+    pub r: f64,
+}
+
 #[derive(Clone)]
 struct Param<T> {
     val: T,
@@ -16,6 +28,10 @@ impl Param<i32> {
         self.val = val.clamp(self.min, self.max);
     }
 
+    fn set_value(&mut self, val: f64) {
+        self.set(val.round() as i32);
+    }
+
     fn info(&self, name: &str) {
         println!(
             "option name {} type spin default {:.0} min {:.0} max {:.0}",
@@ -23,9 +39,20 @@ impl Param<i32> {
         );
     }
 
+    fn tunable(&self, name: &'static str, step: i32, r: f32) -> Tunable {
+        Tunable {
+            name,
+            value: f64::from(self.val),
+            min: f64::from(self.min),
+            max: f64::from(self.max),
+            step: f64::from(step),
+            r: f64::from(r),
+        }
+    }
+
     fn list(&self, name: &str, step: i32, r: f32) {
         println!(
-            "{}, {}, {}, {}, {}, {}",
+            "{}, int, {}, {}, {}, {}, {}",
             name, self.val, self.min, self.max, step, r,
         );
     }
@@ -37,6 +64,10 @@ impl Param<f32> {
         self.val = actual.clamp(self.min, self.max);
     }
 
+    fn set_value(&mut self, val: f64) {
+        self.val = (val as f32).clamp(self.min, self.max);
+    }
+
     fn info(&self, name: &str) {
         println!(
             "option name {} type spin default {:.0} min {:.0} max {:.0}",
@@ -47,9 +78,20 @@ impl Param<f32> {
         );
     }
 
+    fn tunable(&self, name: &'static str, step: f32, r: f32) -> Tunable {
+        Tunable {
+            name,
+            value: f64::from(self.val) * 1000.0,
+            min: f64::from(self.min) * 1000.0,
+            max: f64::from(self.max) * 1000.0,
+            step: f64::from(step) * 1000.0,
+            r: f64::from(r),
+        }
+    }
+
     fn list(&self, name: &str, step: f32, r: f32) {
         println!(
-            "{}, {}, {}, {}, {}, {}",
+            "{}, int, {:.0}, {:.0}, {:.0}, {:.2}, {}",
             name,
             self.val * 1000.0,
             self.min * 1000.0,
@@ -66,6 +108,10 @@ impl Param<f64> {
         self.val = actual.clamp(self.min, self.max);
     }
 
+    fn set_value(&mut self, val: f64) {
+        self.val = val.clamp(self.min, self.max);
+    }
+
     fn info(&self, name: &str) {
         println!(
             "option name {} type spin default {:.0} min {:.0} max {:.0}",
@@ -76,9 +122,20 @@ impl Param<f64> {
         );
     }
 
+    fn tunable(&self, name: &'static str, step: f64, r: f64) -> Tunable {
+        Tunable {
+            name,
+            value: self.val * 1000.0,
+            min: self.min * 1000.0,
+            max: self.max * 1000.0,
+            step: step * 1000.0,
+            r,
+        }
+    }
+
     fn list(&self, name: &str, step: f64, r: f64) {
         println!(
-            "{}, {}, {}, {}, {}, {}",
+            "{}, int, {:.0}, {:.0}, {:.0}, {:.2}, {}",
             name,
             self.val * 1000.0,
             self.min * 1000.0,
@@ -122,9 +179,59 @@ macro_rules! make_mcts_params {
                 }
             }
 
+            /// Every param in the SPSA input format of OpenBench,
+            /// `name, int, value, min, max, step, learning rate`, with
+            /// floats scaled by 1000 as in `info` and `set`.
             pub fn list_spsa(&self) {
                 $(self.$name.list(stringify!($name), $step, $r);)*
             }
+
+            pub fn tunables(&self) -> Vec<Tunable> {
+                vec![$(self.$name.tunable(stringify!($name), $step, $r),)*]
+            }
+
+            /// Every param as `name = value`, unscaled.
+            pub fn to_toml(&self) -> String {
+                let mut toml = String::new();
+                $(toml.push_str(&format!("{} = {:?}\n", stringify!($name), self.$name.val));)*
+                toml
+            }
+
+            /// Set params from lines of `name = value` (unscaled), as
+            /// written by `to_toml`, ignoring comments and blank lines.
+            /// Params that aren't given keep their current values.
+            pub fn apply_toml(&mut self, toml: &str) -> Result<(), String> {
+                for (idx, line) in toml.lines().enumerate() {
+                    let line = line.split('#').next().unwrap_or("").trim();
+
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    let (name, val) = line
+                        .split_once('=')
+                        .ok_or(format!("line {}: expected `name = value`", idx + 1))?;
+
+                    let val = val
+                        .trim()
+                        .parse::<f64>()
+                        .map_err(|_| format!("line {}: invalid value", idx + 1))?;
+
+                    match name.trim() {
+                        $(stringify!($name) => self.$name.set_value(val),)*
+                        name => return Err(format!("line {}: unknown param {name}", idx + 1)),
+                    }
+                }
+
+                Ok(())
+            }
+
+            pub fn load(path: &str) -> Result<Self, String> {
+                let toml = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+                let mut params = Self::default();
+                params.apply_toml(&toml)?;
+                Ok(params)
+            }
         }
     };
 }
@@ -135,8 +242,12 @@ make_mcts_params! {
     winning_pst_threshold: f32 = 0.603, 0.0, 1.0, 0.05, 0.002;
     winning_pst_max: f32 = 1.615, 0.1, 10.0, 0.1, 0.002;
     base_pst_adjustment: f32 = 0.1, 0.01, 1.0, 0.01, 0.002;
+    root_policy_temperature: f32 = 1.0, 0.1, 4.0, 0.1, 0.002;
+    policy_temperature: f32 = 1.0, 0.1, 4.0, 0.1, 0.002;
     root_cpuct: f32 = 0.422, 0.1, 5.0, 0.065, 0.002;
     cpuct: f32 = 0.269, 0.1, 5.0, 0.065, 0.002;
+    fpu_base: f32 = 0.0, -0.5, 0.5, 0.02, 0.002;
+    fpu_reduction: f32 = 0.0, 0.0, 1.0, 0.05, 0.002;
     cpuct_var_weight: f32 = 0.808, 0.0, 2.0, 0.085, 0.002;
     cpuct_var_scale: f32 = 0.278, 0.0, 2.0, 0.02, 0.002;
     cpuct_var_warmup: f32 = 0.5, 0.0, 1.0, 0.01, 0.002;
@@ -145,6 +256,22 @@ make_mcts_params! {
     gini_base: f32 = 0.463, 0.2, 2.0, 0.0679, 0.002;
     gini_ln_multiplier: f32 = 1.567, 0.4, 3.0, 0.1634, 0.002;
     gini_min: f32 = 2.26, 0.5, 4.0, 0.21, 0.002;
+    policy_entropy_threshold: f32 = 0.9, 0.0, 1.0, 0.05, 0.002;
+    heuristic_prior_weight: f32 = 0.0, 0.0, 1.0, 0.05, 0.002;
+    tree_reuse_decay: f32 = 1.0, 0.0, 1.0, 0.05, 0.002;
+    opponent_prior_weight: f32 = 0.1, 0.0, 1.0, 0.05, 0.002;
+    transposition_seed_visits: i32 = 0, 0, 16, 1, 0.002;
+    widening_base: f32 = 4.0, 1.0, 32.0, 0.5, 0.002;
+    widening_scale: f32 = 1.0, 0.1, 8.0, 0.1, 0.002;
+    widening_exponent: f32 = 0.5, 0.1, 1.0, 0.05, 0.002;
+    corrhist_material_weight: f32 = 0.0, 0.0, 2.0, 0.1, 0.002;
+    corrhist_pawn_weight: f32 = 0.0, 0.0, 2.0, 0.1, 0.002;
+    corrhist_decay: f32 = 0.5, 0.0, 1.0, 0.05, 0.002;
+    corrhist_update_visits: i32 = 64, 8, 1024, 8, 0.002;
+    gumbel_considered: i32 = 16, 2, 64, 2, 0.002;
+    gumbel_c_visit: f32 = 50.0, 1.0, 200.0, 5.0, 0.002;
+    gumbel_c_scale: f32 = 1.0, 0.1, 10.0, 0.1, 0.002;
+    eval_scale: f32 = 400.0, 200.0, 800.0, 20.0, 0.002;
     knight_value: i32 = 437, 250, 750, 25, 0.002;
     bishop_value: i32 = 409, 250, 750, 25, 0.002;
     rook_value: i32 = 768, 400, 1000, 30, 0.002;
@@ -180,4 +307,7 @@ make_mcts_params! {
     tm_bmv4: f32 = 2.561, 0.1, 8.0, 0.4, 0.002;
     tm_bmv5: f32 = 0.634, 0.1, 1.0, 0.055, 0.002;
     tm_bmv6: f32 = 1.894, 0.1, 3.0, 0.15, 0.002;
+    tm_easy_time: f32 = 0.1, 0.01, 0.5, 0.02, 0.002;
+    tm_easy_visits: f32 = 0.9, 0.5, 1.0, 0.03, 0.002;
+    tm_easy_policy: f32 = 0.8, 0.3, 1.0, 0.05, 0.002;
 }
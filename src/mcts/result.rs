@@ -0,0 +1,153 @@
+use crate::{
+    chess::{ChessState, Move},
+    score::Score,
+};
+
+/// Score of a root move, and the move, Q and visits of each node along its PV.
+pub(super) type Line = (Score, Vec<(Move, f32, i32)>);
+
+/// A move in a principal variation, as seen by the search.
+#[derive(Clone, Debug)]
+pub struct PvMove {
+    pub mov: Move,
+    pub uci: String,
+    pub san: String,
+    /// Expected score for the side playing the move.
+    pub q: f32,
+    pub visits: i32,
+}
+
+/// A principal variation starting with one of the root moves.
+#[derive(Clone, Debug)]
+pub struct Variation {
+    pub score: Score,
+    pub moves: Vec<PvMove>,
+}
+
+/// Outcome of a search, for use without going through UCI output.
+pub struct SearchResult {
+    pub best_move: Move,
+    /// Expected score of the best move for the side to move.
+    pub q: f32,
+    /// Milliseconds into the search when the best move was (last) found.
+    pub best_move_time: u128,
+    /// Nodes searched.
+    pub nodes: usize,
+    root: ChessState,
+    lines: Vec<Line>,
+}
+
+impl SearchResult {
+    /// `lines` holds the PV of every visited root move, best first.
+    pub(super) fn new(best_move: Move, q: f32, root: ChessState, lines: Vec<Line>) -> Self {
+        Self {
+            best_move,
+            q,
+            best_move_time: 0,
+            nodes: 0,
+            root,
+            lines,
+        }
+    }
+
+    /// A root move sampled in proportion to `visits ^ (1 / temperature)`,
+    /// with `uniform` a random number in `[0, 1)`. Proven wins are always
+    /// played, as is the best move with a temperature of 0.
+    pub fn sample_move(&self, temperature: f32, uniform: f32) -> Move {
+        let best_wins = matches!(self.lines.first(), Some((Score::Mate(n), _)) if *n > 0);
+
+        if temperature <= 0.0 || best_wins {
+            return self.best_move;
+        }
+
+        let max_visits = self
+            .lines
+            .iter()
+            .map(|(_, line)| line[0].2)
+            .max()
+            .unwrap_or(0);
+
+        if max_visits == 0 {
+            return self.best_move;
+        }
+
+        let weights = self
+            .lines
+            .iter()
+            .map(|(_, line)| {
+                let (mov, _, visits) = line[0];
+                (
+                    mov,
+                    (visits as f32 / max_visits as f32).powf(1.0 / temperature),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        sample(&weights, uniform).unwrap_or(self.best_move)
+    }
+
+    /// A root move other than the best, sampled in proportion to visits,
+    /// for deliberately weaker play. Proven wins are still played, and the
+    /// best move if no other has been visited.
+    pub fn sample_inferior_move(&self, uniform: f32) -> Move {
+        let best_wins = matches!(self.lines.first(), Some((Score::Mate(n), _)) if *n > 0);
+
+        if best_wins {
+            return self.best_move;
+        }
+
+        let weights = self
+            .lines
+            .iter()
+            .map(|(_, line)| (line[0].0, line[0].2 as f32))
+            .filter(|&(mov, visits)| mov != self.best_move && visits > 0.0)
+            .collect::<Vec<_>>();
+
+        sample(&weights, uniform).unwrap_or(self.best_move)
+    }
+
+    /// The (at most) `n` best variations, best first.
+    pub fn variations(&self, n: usize) -> Vec<Variation> {
+        self.lines
+            .iter()
+            .take(n)
+            .map(|(score, line)| {
+                let mut pos = self.root.clone();
+                let mut moves = Vec::with_capacity(line.len());
+
+                for &(mov, q, visits) in line {
+                    moves.push(PvMove {
+                        mov,
+                        uci: pos.conv_mov_to_str(mov),
+                        san: pos.conv_mov_to_san(mov),
+                        q,
+                        visits,
+                    });
+
+                    pos.make_move(mov);
+                }
+
+                Variation {
+                    score: *score,
+                    moves,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A move sampled in proportion to its weight, with `uniform` in `[0, 1)`.
+fn sample(weights: &[(Move, f32)], uniform: f32) -> Option<Move> {
+    let total = weights.iter().map(|&(_, weight)| weight).sum::<f32>();
+    let mut target = uniform * total;
+
+    for &(mov, weight) in weights {
+        if target < weight {
+            return Some(mov);
+        }
+
+        target -= weight;
+    }
+
+    None
+}
@@ -0,0 +1,148 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+/// Orders the playouts of a multi-threaded search (`ReproSMP`), so that
+/// with a node limit the resulting tree does not depend on thread timing.
+///
+/// Each playout takes a ticket, and with `window` threads the selection
+/// and backpropagation phases of ticket `k` are made to happen strictly in
+/// the order `S(0), .., S(window - 1), B(0), S(window), B(1), S(window + 1), ..`
+/// so every selection sees exactly the same in-flight playouts (virtual
+/// losses) and completed backpropagations. Evaluation of the leaf happens
+/// between the two phases, and may overlap with other playouts.
+///
+/// In round-robin mode (`RoundRobinSMP`) the window is 1, so playouts run
+/// strictly one after another, and ticket `k` is always performed by
+/// thread `k % threads`, so that a run can be replayed exactly, down to
+/// which thread touched which node.
+pub struct Sequencer {
+    window: usize,
+    /// Number of threads taking tickets in turn, in round-robin mode.
+    turns: Option<usize>,
+    limit: usize,
+    next_ticket: AtomicUsize,
+    progress: Mutex<Progress>,
+    changed: Condvar,
+    stopped: AtomicBool,
+}
+
+#[derive(Default)]
+struct Progress {
+    selected: usize,
+    backpropagated: usize,
+}
+
+impl Sequencer {
+    pub fn new(window: usize, limit: usize) -> Self {
+        Self {
+            window,
+            turns: None,
+            limit,
+            next_ticket: AtomicUsize::new(0),
+            progress: Mutex::new(Progress::default()),
+            changed: Condvar::new(),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    pub fn round_robin(threads: usize, limit: usize) -> Self {
+        Self {
+            turns: Some(threads),
+            ..Self::new(1, limit)
+        }
+    }
+
+    /// Prepare for a new round of playouts after the tree has been flipped,
+    /// reissuing the tickets of any playouts that were discarded.
+    pub fn restart(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.selected = progress.backpropagated;
+        self.next_ticket
+            .store(progress.backpropagated, Ordering::Relaxed);
+        self.stopped.store(false, Ordering::Relaxed);
+    }
+
+    /// Wake up and fail all waiting playouts, as the round cannot continue
+    /// (e.g. the tree is full).
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.changed.notify_all();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Next playout for `thread` to perform, or `None` if the node limit
+    /// has been reached (or, in round-robin mode, the round was stopped
+    /// while waiting for its turn).
+    pub fn take_ticket(&self, thread: usize, abort: &AtomicBool) -> Option<usize> {
+        if let Some(turns) = self.turns {
+            let my_turn = self.wait_until(abort, |_| {
+                let ticket = self.next_ticket.load(Ordering::Relaxed);
+                ticket >= self.limit || ticket % turns == thread
+            });
+
+            if !my_turn {
+                return None;
+            }
+        }
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        self.changed.notify_all();
+        (ticket < self.limit).then_some(ticket)
+    }
+
+    pub fn begin_selection(&self, ticket: usize, abort: &AtomicBool) -> bool {
+        self.wait_until(abort, |progress| {
+            progress.selected == ticket && progress.backpropagated + self.window > ticket
+        })
+    }
+
+    pub fn end_selection(&self) {
+        self.progress.lock().unwrap().selected += 1;
+        self.changed.notify_all();
+    }
+
+    pub fn begin_backprop(&self, ticket: usize, abort: &AtomicBool) -> bool {
+        let selections = (ticket + self.window).min(self.limit);
+
+        self.wait_until(abort, |progress| {
+            progress.backpropagated == ticket && progress.selected >= selections
+        })
+    }
+
+    pub fn end_backprop(&self) {
+        self.progress.lock().unwrap().backpropagated += 1;
+        self.changed.notify_all();
+    }
+
+    /// Wait for every playout below the node limit to complete.
+    pub fn wait_until_done(&self, abort: &AtomicBool) {
+        self.wait_until(abort, |progress| progress.backpropagated >= self.limit);
+    }
+
+    fn wait_until<F: Fn(&Progress) -> bool>(&self, abort: &AtomicBool, ready: F) -> bool {
+        let mut progress = self.progress.lock().unwrap();
+
+        while !ready(&progress) {
+            if self.stopped.load(Ordering::Relaxed) || abort.load(Ordering::Relaxed) {
+                return false;
+            }
+
+            // `abort` is set without notifying, so poll for it
+            progress = self
+                .changed
+                .wait_timeout(progress, Duration::from_millis(1))
+                .unwrap()
+                .0;
+        }
+
+        true
+    }
+}
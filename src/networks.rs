@@ -1,7 +1,9 @@
 mod accumulator;
 mod activation;
+pub mod checksum;
 mod layer;
 mod policy;
+pub mod safetensors;
 mod threats;
 mod value;
 
@@ -2,6 +2,7 @@ pub trait Activation {
     fn activate(x: f32) -> f32;
 }
 
+#[allow(dead_code)]
 pub struct ReLU;
 impl Activation for ReLU {
     #[inline]
@@ -0,0 +1,63 @@
+//! Networks are published as `nn-<prefix>.network`, the prefix being the
+//! first 12 hex digits of the SHA-256 of the file, so that a corrupted or
+//! truncated file can be told apart from the network it should be.
+//!
+//! Not every file named like this follows the convention: those that the
+//! embed build extracts are named after the SHA-256 of the compressed
+//! network, so only known published names are checked.
+
+use std::{fs::File, io, path::Path};
+
+use sha2::{Digest, Sha256};
+
+const PREFIX_LEN: usize = 12;
+
+/// The name under which a network of these bytes is published.
+pub fn file_name(bytes: &[u8]) -> String {
+    format!("nn-{}.network", &hex(&Sha256::digest(bytes))[..PREFIX_LEN])
+}
+
+/// Check a network against the prefix in its file name, if that is
+/// `published_name` (e.g. `PolicyFileDefaultName`).
+pub fn verify(path: &str, published_name: &str) -> Result<(), String> {
+    let Some(expected) = Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|&name| name == published_name)
+        .and_then(expected_prefix)
+    else {
+        return Ok(());
+    };
+
+    let mut hasher = Sha256::new();
+    File::open(path)
+        .and_then(|mut file| io::copy(&mut file, &mut hasher))
+        .map_err(|err| err.to_string())?;
+
+    let actual = hex(&hasher.finalize());
+
+    if actual.starts_with(expected) {
+        Ok(())
+    } else {
+        Err(format!(
+            "SHA-256 {} does not match its name, the file is corrupted or truncated",
+            &actual[..PREFIX_LEN]
+        ))
+    }
+}
+
+fn expected_prefix(name: &str) -> Option<&str> {
+    let prefix = name.strip_prefix("nn-")?.strip_suffix(".network")?;
+
+    let is_hex = |b: u8| matches!(b, b'0'..=b'9' | b'a'..=b'f');
+
+    if prefix.len() == PREFIX_LEN && prefix.bytes().all(is_hex) {
+        Some(prefix)
+    } else {
+        None
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
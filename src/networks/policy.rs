@@ -1,11 +1,15 @@
 use crate::{
     boxed_and_zeroed,
     chess::{Attacks, Board, Move},
+    profile::{self, Phase},
 };
 
+use std::mem::offset_of;
+
 use super::{
     accumulator::Accumulator,
     layer::{Layer, TransposedLayer},
+    safetensors::{Dtype, SafeTensors, TensorSpec},
 };
 
 // DO NOT MOVE
@@ -27,46 +31,71 @@ pub struct PolicyNetwork {
     l2: TransposedLayer<i8, { L1 / 2 }, { 1880 * 2 }>,
 }
 
+// every field is a tensor of plain numbers
+unsafe impl SafeTensors for PolicyNetwork {
+    const TENSORS: &'static [TensorSpec] = &[
+        TensorSpec::layer(
+            "l1.weight",
+            Dtype::I8,
+            768 * 4,
+            L1,
+            offset_of!(Self, l1.weights),
+        ),
+        TensorSpec::vector("l1.bias", Dtype::I8, L1, offset_of!(Self, l1.biases)),
+        TensorSpec::matrix(
+            "l2.weight",
+            Dtype::I8,
+            [1880 * 2, L1 / 2],
+            offset_of!(Self, l2.weights),
+        ),
+        TensorSpec::vector("l2.bias", Dtype::I8, 1880 * 2, offset_of!(Self, l2.biases)),
+    ];
+}
+
 impl PolicyNetwork {
     pub fn hl(&self, pos: &Board) -> Accumulator<i16, { L1 / 2 }> {
-        let mut l1 = Accumulator([0; L1]);
-
-        for (r, &b) in l1.0.iter_mut().zip(self.l1.biases.0.iter()) {
-            *r = i16::from(b);
-        }
+        profile::time(Phase::PolicyHidden, || {
+            let mut l1 = Accumulator([0; L1]);
 
-        pos.map_features(|feat| {
-            for (r, &w) in l1.0.iter_mut().zip(self.l1.weights[feat].0.iter()) {
-                *r += i16::from(w);
+            for (r, &b) in l1.0.iter_mut().zip(self.l1.biases.0.iter()) {
+                *r = i16::from(b);
             }
-        });
 
-        let mut res = Accumulator([0; L1 / 2]);
-
-        for (elem, (&i, &j)) in res
-            .0
-            .iter_mut()
-            .zip(l1.0.iter().take(L1 / 2).zip(l1.0.iter().skip(L1 / 2)))
-        {
-            let i = i32::from(i).clamp(0, i32::from(QA));
-            let j = i32::from(j).clamp(0, i32::from(QA));
-            *elem = ((i * j) / i32::from(QA / FACTOR)) as i16;
-        }
+            pos.map_features(|feat| {
+                for (r, &w) in l1.0.iter_mut().zip(self.l1.weights[feat].0.iter()) {
+                    *r += i16::from(w);
+                }
+            });
+
+            let mut res = Accumulator([0; L1 / 2]);
+
+            for (elem, (&i, &j)) in res
+                .0
+                .iter_mut()
+                .zip(l1.0.iter().take(L1 / 2).zip(l1.0.iter().skip(L1 / 2)))
+            {
+                let i = i32::from(i).clamp(0, i32::from(QA));
+                let j = i32::from(j).clamp(0, i32::from(QA));
+                *elem = ((i * j) / i32::from(QA / FACTOR)) as i16;
+            }
 
-        res
+            res
+        })
     }
 
     pub fn get(&self, pos: &Board, mov: &Move, hl: &Accumulator<i16, { L1 / 2 }>) -> f32 {
-        let idx = map_move_to_index(pos, *mov);
-        let weights = &self.l2.weights[idx];
+        profile::time(Phase::PolicyOutput, || {
+            let idx = map_move_to_index(pos, *mov);
+            let weights = &self.l2.weights[idx];
 
-        let mut res = 0;
+            let mut res = 0;
 
-        for (&w, &v) in weights.0.iter().zip(hl.0.iter()) {
-            res += i32::from(w) * i32::from(v);
-        }
+            for (&w, &v) in weights.0.iter().zip(hl.0.iter()) {
+                res += i32::from(w) * i32::from(v);
+            }
 
-        (res as f32 / f32::from(QA * FACTOR) + f32::from(self.l2.biases.0[idx])) / f32::from(QB)
+            (res as f32 / f32::from(QA * FACTOR) + f32::from(self.l2.biases.0[idx])) / f32::from(QB)
+        })
     }
 }
 
@@ -128,4 +157,36 @@ impl UnquantisedPolicyNetwork {
 
         quantised
     }
+
+    /// As `PolicyNetwork::hl`, without quantisation.
+    pub fn hl(&self, pos: &Board) -> Accumulator<f32, { L1 / 2 }> {
+        let mut l1 = self.l1.biases;
+
+        pos.map_features(|feat| l1.add(&self.l1.weights[feat]));
+
+        let mut res = Accumulator([0.0; L1 / 2]);
+
+        for (elem, (&i, &j)) in res
+            .0
+            .iter_mut()
+            .zip(l1.0.iter().take(L1 / 2).zip(l1.0.iter().skip(L1 / 2)))
+        {
+            *elem = i.clamp(0.0, 1.0) * j.clamp(0.0, 1.0);
+        }
+
+        res
+    }
+
+    /// As `PolicyNetwork::get`, without quantisation.
+    pub fn get(&self, pos: &Board, mov: &Move, hl: &Accumulator<f32, { L1 / 2 }>) -> f32 {
+        let idx = map_move_to_index(pos, *mov);
+
+        let mut res = self.l2.biases.0[idx];
+
+        for (weights, &v) in self.l2.weights.iter().zip(hl.0.iter()) {
+            res += weights.0[idx] * v;
+        }
+
+        res
+    }
 }
@@ -0,0 +1,376 @@
+//! Loading networks from `.safetensors` files, as written by PyTorch and
+//! JAX, in place of the raw memory images.
+//!
+//! Each network lists its tensors by name, with the dtype and shape they
+//! must have. Weights of ordinary layers are `[outputs, inputs]` like a
+//! PyTorch `Linear`, and transposed into place. Values must already be
+//! quantised in the same way as the raw format, and tensors not listed
+//! are ignored.
+//!
+//! Unlike raw images, which are used in place from their mapping, these
+//! are copied into a network in memory, as layers need transposing. The
+//! file is mapped rather than read so that it isn't held in memory too.
+
+use std::{collections::HashMap, fs::File};
+
+use memmap2::Mmap;
+
+use crate::boxed_and_zeroed;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dtype {
+    I8,
+    I16,
+    F32,
+}
+
+impl Dtype {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "I8" => Some(Dtype::I8),
+            "I16" => Some(Dtype::I16),
+            "F32" => Some(Dtype::F32),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Dtype::I8 => 1,
+            Dtype::I16 => 2,
+            Dtype::F32 => 4,
+        }
+    }
+}
+
+/// A tensor that a network expects, stored `offset` bytes into it.
+pub struct TensorSpec {
+    pub name: &'static str,
+    pub dtype: Dtype,
+    /// `[rows, columns]`, or `[1, len]` for a vector.
+    pub shape: [usize; 2],
+    /// Whether it is stored as `[columns, rows]` in the network.
+    pub transpose: bool,
+    pub offset: usize,
+}
+
+impl TensorSpec {
+    /// Stored as it is in the file, e.g. the weights of a `TransposedLayer`.
+    pub const fn matrix(
+        name: &'static str,
+        dtype: Dtype,
+        shape: [usize; 2],
+        offset: usize,
+    ) -> Self {
+        Self {
+            name,
+            dtype,
+            shape,
+            transpose: false,
+            offset,
+        }
+    }
+
+    /// Weights of a `Layer`, `[outputs, inputs]` in the file.
+    pub const fn layer(
+        name: &'static str,
+        dtype: Dtype,
+        inputs: usize,
+        outputs: usize,
+        offset: usize,
+    ) -> Self {
+        Self {
+            name,
+            dtype,
+            shape: [outputs, inputs],
+            transpose: true,
+            offset,
+        }
+    }
+
+    pub const fn vector(name: &'static str, dtype: Dtype, len: usize, offset: usize) -> Self {
+        Self::matrix(name, dtype, [1, len], offset)
+    }
+}
+
+/// # Safety
+/// Every byte of the network must be covered by `TENSORS`, or be valid
+/// if zeroed, and any bytes must be valid for the tensors' types.
+pub unsafe trait SafeTensors: Sized {
+    const TENSORS: &'static [TensorSpec];
+}
+
+/// Where a tensor's data is in the file.
+struct TensorInfo {
+    dtype: String,
+    shape: Vec<usize>,
+    data: (usize, usize),
+}
+
+pub fn load<T: SafeTensors>(path: &str) -> Result<Box<T>, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    // as with raw networks, the file must not change while mapped
+    let bytes = unsafe { Mmap::map(&file) }.map_err(|err| err.to_string())?;
+
+    let header_len = bytes
+        .get(..8)
+        .map(|len| u64::from_le_bytes(len.try_into().unwrap()))
+        .ok_or("missing header")?;
+
+    // the length is untrusted, so mustn't overflow
+    let header_end = usize::try_from(header_len)
+        .ok()
+        .and_then(|len| len.checked_add(8))
+        .ok_or("invalid header length")?;
+
+    let header = bytes
+        .get(8..header_end)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or("invalid header")?;
+
+    let data = &bytes[header_end..];
+    let tensors = parse_header(header)?;
+
+    // safe by the contract of `SafeTensors`
+    let mut net: Box<T> = unsafe { boxed_and_zeroed() };
+    let base = (net.as_mut() as *mut T).cast::<u8>();
+
+    for spec in T::TENSORS {
+        let info = tensors
+            .get(spec.name)
+            .ok_or(format!("missing tensor {}", spec.name))?;
+
+        let shape_ok = if spec.shape[0] == 1 {
+            info.shape == [spec.shape[1]] || info.shape == spec.shape
+        } else {
+            info.shape == spec.shape
+        };
+
+        if Dtype::parse(&info.dtype) != Some(spec.dtype) || !shape_ok {
+            return Err(format!(
+                "tensor {} is {} {:?}, expected {:?} {:?}",
+                spec.name, info.dtype, info.shape, spec.dtype, spec.shape
+            ));
+        }
+
+        let size = spec.dtype.size();
+        let [rows, cols] = spec.shape;
+        let (start, end) = info.data;
+
+        let src = data
+            .get(start..end)
+            .filter(|src| src.len() == rows * cols * size)
+            .ok_or(format!("tensor {} is truncated", spec.name))?;
+
+        assert!(spec.offset + src.len() <= std::mem::size_of::<T>());
+        let dst = unsafe { std::slice::from_raw_parts_mut(base.add(spec.offset), src.len()) };
+
+        if spec.transpose {
+            for (i, elem) in src.chunks_exact(size).enumerate() {
+                let (row, col) = (i / cols, i % cols);
+                let idx = (col * rows + row) * size;
+                dst[idx..idx + size].copy_from_slice(elem);
+            }
+        } else {
+            dst.copy_from_slice(src);
+        }
+    }
+
+    Ok(net)
+}
+
+/// The header is a JSON object of tensors by name, each with `dtype`,
+/// `shape` and `data_offsets`, and an optional `__metadata__`.
+fn parse_header(header: &str) -> Result<HashMap<String, TensorInfo>, String> {
+    let mut parser = Parser {
+        text: header.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+
+    let Json::Object(entries) = parser.value()? else {
+        return Err("header is not an object".to_string());
+    };
+
+    // headers may be padded with spaces, but nothing else
+    if parser.peek().is_some() {
+        return Err(format!("trailing data at byte {}", parser.pos));
+    }
+
+    let mut tensors = HashMap::new();
+
+    for (name, value) in entries {
+        if name == "__metadata__" {
+            continue;
+        }
+
+        let invalid = || format!("invalid entry for tensor {name}");
+        let Json::Object(fields) = value else {
+            return Err(invalid());
+        };
+
+        let field = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        let numbers = |value: Option<&Json>| match value {
+            Some(Json::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Json::Number(n) => Some(*n),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+
+        let Some(Json::String(dtype)) = field("dtype") else {
+            return Err(invalid());
+        };
+
+        let shape = numbers(field("shape")).ok_or_else(invalid)?;
+        let offsets = numbers(field("data_offsets")).ok_or_else(invalid)?;
+
+        let [start, end] = offsets[..] else {
+            return Err(invalid());
+        };
+
+        let info = TensorInfo {
+            dtype: dtype.clone(),
+            shape,
+            data: (start, end),
+        };
+
+        tensors.insert(name, info);
+    }
+
+    Ok(tensors)
+}
+
+/// As much of JSON as safetensors headers use.
+enum Json {
+    Object(Vec<(String, Json)>),
+    Array(Vec<Json>),
+    String(String),
+    Number(usize),
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    /// Objects and arrays currently open.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    /// Far deeper than any valid header nests, but shallow enough that
+    /// recursing to it can't overflow the stack.
+    const MAX_DEPTH: usize = 16;
+
+    fn open(&mut self) -> Result<(), String> {
+        self.pos += 1;
+        self.depth += 1;
+
+        if self.depth > Self::MAX_DEPTH {
+            return Err(format!("nested too deeply at byte {}", self.pos));
+        }
+
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at byte {}", byte as char, self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some(b'{') => {
+                self.open()?;
+                let mut entries = Vec::new();
+
+                if self.peek() != Some(b'}') {
+                    loop {
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        entries.push((key, self.value()?));
+
+                        if self.peek() != Some(b',') {
+                            break;
+                        }
+
+                        self.pos += 1;
+                    }
+                }
+
+                self.expect(b'}')?;
+                self.depth -= 1;
+                Ok(Json::Object(entries))
+            }
+            Some(b'[') => {
+                self.open()?;
+                let mut items = Vec::new();
+
+                if self.peek() != Some(b']') {
+                    loop {
+                        items.push(self.value()?);
+
+                        if self.peek() != Some(b',') {
+                            break;
+                        }
+
+                        self.pos += 1;
+                    }
+                }
+
+                self.expect(b']')?;
+                self.depth -= 1;
+                Ok(Json::Array(items))
+            }
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'0'..=b'9') => {
+                let start = self.pos;
+
+                while self.text.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                    self.pos += 1;
+                }
+
+                let digits = std::str::from_utf8(&self.text[start..self.pos]).unwrap();
+                digits
+                    .parse()
+                    .map(Json::Number)
+                    .map_err(|err| err.to_string())
+            }
+            _ => Err(format!("unexpected value at byte {}", self.pos)),
+        }
+    }
+
+    /// Escapes are kept as they are, as names and dtypes don't need them.
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let start = self.pos;
+
+        while let Some(&byte) = self.text.get(self.pos) {
+            match byte {
+                b'"' => {
+                    let string = String::from_utf8_lossy(&self.text[start..self.pos]);
+                    self.pos += 1;
+                    return Ok(string.into_owned());
+                }
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+
+        Err("unterminated string".to_string())
+    }
+}
@@ -1,8 +1,15 @@
-use crate::chess::Board;
+use crate::{
+    chess::Board,
+    profile::{self, Phase},
+    score::Wdl,
+};
+
+use std::mem::offset_of;
 
 use super::{
     activation::SCReLU,
     layer::{Layer, TransposedLayer},
+    safetensors::{Dtype, SafeTensors, TensorSpec},
     threats, Accumulator,
 };
 
@@ -27,22 +34,73 @@ pub struct ValueNetwork {
     l4: Layer<f32, 128, 3>,
 }
 
+// every field is a tensor of plain numbers
+unsafe impl SafeTensors for ValueNetwork {
+    const TENSORS: &'static [TensorSpec] = &[
+        TensorSpec::matrix(
+            "pst",
+            Dtype::F32,
+            [threats::TOTAL, 3],
+            offset_of!(Self, pst),
+        ),
+        TensorSpec::layer(
+            "l1.weight",
+            Dtype::I16,
+            threats::TOTAL,
+            L1,
+            offset_of!(Self, l1.weights),
+        ),
+        TensorSpec::vector("l1.bias", Dtype::I16, L1, offset_of!(Self, l1.biases)),
+        TensorSpec::matrix(
+            "l2.weight",
+            Dtype::I16,
+            [16, L1 / 2],
+            offset_of!(Self, l2.weights),
+        ),
+        TensorSpec::vector("l2.bias", Dtype::I16, 16, offset_of!(Self, l2.biases)),
+        TensorSpec::layer(
+            "l3.weight",
+            Dtype::F32,
+            16,
+            128,
+            offset_of!(Self, l3.weights),
+        ),
+        TensorSpec::vector("l3.bias", Dtype::F32, 128, offset_of!(Self, l3.biases)),
+        TensorSpec::layer(
+            "l4.weight",
+            Dtype::F32,
+            128,
+            3,
+            offset_of!(Self, l4.weights),
+        ),
+        TensorSpec::vector("l4.bias", Dtype::F32, 3, offset_of!(Self, l4.biases)),
+    ];
+}
+
 impl ValueNetwork {
-    pub fn eval(&self, board: &Board) -> (f32, f32, f32) {
-        let mut pst = Accumulator([0.0; 3]);
-
-        let mut count = 0;
-        let mut feats = [0; 160];
-        threats::map_features(board, |feat| {
-            feats[count] = feat;
-            pst.add(&self.pst[feat]);
-            count += 1;
-        });
+    pub fn eval(&self, board: &Board) -> Wdl {
+        let (pst, l2) = profile::time(Phase::ValueFeatures, || {
+            let mut pst = Accumulator([0.0; 3]);
+
+            let mut count = 0;
+            let mut feats = [0; 160];
+            threats::map_features(board, |feat| {
+                feats[count] = feat;
+                pst.add(&self.pst[feat]);
+                count += 1;
+            });
 
-        let mut l2 = self.l1.biases;
+            let mut l2 = self.l1.biases;
 
-        l2.add_multi(&feats[..count], &self.l1.weights);
+            l2.add_multi(&feats[..count], &self.l1.weights);
 
+            (pst, l2)
+        });
+
+        profile::time(Phase::ValueLayers, || self.layers(&pst, &l2))
+    }
+
+    fn layers(&self, pst: &Accumulator<f32, 3>, l2: &Accumulator<i16, L1>) -> Wdl {
         let mut act = [0; L1 / 2];
 
         for (a, (&i, &j)) in act
@@ -70,7 +128,7 @@ impl ValueNetwork {
 
         let l4 = self.l3.forward::<SCReLU>(&l3);
         let mut out = self.l4.forward::<SCReLU>(&l4);
-        out.add(&pst);
+        out.add(pst);
 
         let mut win = out.0[2];
         let mut draw = out.0[1];
@@ -84,6 +142,10 @@ impl ValueNetwork {
 
         let sum = win + draw + loss;
 
-        (win / sum, draw / sum, loss / sum)
+        Wdl {
+            win: win / sum,
+            draw: draw / sum,
+            loss: loss / sum,
+        }
     }
 }
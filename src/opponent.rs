@@ -0,0 +1,186 @@
+//! Information about the opponent, as given by the GUI or a bot frontend
+//! through `UCI_Opponent`, and a record of the moves they have played.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chess::{ChessState, Move},
+    mcts::SearchOptions,
+    score,
+};
+
+/// Rough rating of the engine at full strength, for comparing with opponents.
+pub const ENGINE_ELO: i32 = 3400;
+
+/// Called whenever the opponent changes with `AutoContempt` enabled, so that
+/// frontends embedding the engine can customise the search per opponent.
+pub type OpponentHook = fn(&Opponent, &mut SearchOptions);
+
+/// Default `OpponentHook`: play for a win against lower rated opponents.
+pub fn rating_contempt(opponent: &Opponent, options: &mut SearchOptions) {
+    let cp = opponent.auto_contempt(ENGINE_ELO);
+    options.contempt = score::eval_cp_to_q(cp, score::EVAL_SCALE) - 0.5;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Opponent {
+    pub title: Option<String>,
+    pub elo: Option<i32>,
+    pub computer: bool,
+    pub name: String,
+}
+
+impl Opponent {
+    /// Parses the value of `UCI_Opponent`, `<title> <elo> <computer|human> <name>`,
+    /// where the title and elo may be `none`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.splitn(4, ' ');
+
+        let title = parts.next()?;
+        let elo = parts.next()?;
+        let kind = parts.next()?;
+        let name = parts.next().unwrap_or("").trim();
+
+        Some(Self {
+            title: (title != "none").then(|| title.to_string()),
+            elo: elo.parse().ok(),
+            computer: kind == "computer",
+            name: name.to_string(),
+        })
+    }
+
+    /// Contempt (in internal centipawns) to use against this opponent, by
+    /// how much lower rated they are than `own_elo`, 0 if unrated.
+    pub fn auto_contempt(&self, own_elo: i32) -> i32 {
+        self.elo
+            .map_or(0, |elo| ((own_elo - elo) / 20).clamp(0, 100))
+    }
+}
+
+/// Moves an opponent has played in previous games, by position hash.
+#[derive(Default)]
+pub struct OpponentProfile {
+    path: Option<PathBuf>,
+    moves: HashMap<u64, Vec<(Move, u32)>>,
+}
+
+impl OpponentProfile {
+    /// The profile of `opponent` kept in `dir`, empty if there is none yet.
+    ///
+    /// Each line of the file is `<position hash> <move> <count>`.
+    pub fn load(dir: &str, opponent: &Opponent) -> Self {
+        let file_name = opponent
+            .name
+            .chars()
+            .map(|ch| {
+                if ch.is_ascii_alphanumeric() || ch == '-' {
+                    ch
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        let path = Path::new(dir).join(format!("{file_name}.profile"));
+        let mut profile = Self {
+            path: Some(path.clone()),
+            moves: HashMap::new(),
+        };
+
+        if let Ok(contents) = fs::read_to_string(&path) {
+            for line in contents.lines() {
+                let parts = line.split_whitespace().collect::<Vec<_>>();
+
+                if let [hash, mov, count] = parts[..] {
+                    if let (Ok(hash), Ok(mov), Ok(count)) = (
+                        u64::from_str_radix(hash, 16),
+                        mov.parse::<u16>(),
+                        count.parse::<u32>(),
+                    ) {
+                        profile.add(hash, Move::from(mov), count);
+                    }
+                }
+            }
+        }
+
+        profile
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.path.is_some()
+    }
+
+    fn add(&mut self, hash: u64, mov: Move, count: u32) {
+        let moves = self.moves.entry(hash).or_default();
+
+        if let Some(entry) = moves.iter_mut().find(|(m, _)| *m == mov) {
+            entry.1 += count;
+        } else {
+            moves.push((mov, count));
+        }
+    }
+
+    /// Record that the opponent played `mov` in the position with `hash`.
+    pub fn record(&mut self, hash: u64, mov: Move) {
+        self.add(hash, mov, 1);
+    }
+
+    /// How often the opponent played each move in the position with `hash`,
+    /// as a fraction of the times they reached it.
+    pub fn frequencies(&self, hash: u64) -> Vec<(Move, f32)> {
+        let Some(moves) = self.moves.get(&hash) else {
+            return Vec::new();
+        };
+
+        let total = moves.iter().map(|&(_, count)| count).sum::<u32>() as f32;
+
+        moves
+            .iter()
+            .map(|&(mov, count)| (mov, count as f32 / total))
+            .collect()
+    }
+
+    /// `frequencies` of the opponent's replies to each legal move in `pos`,
+    /// for those moves after which they have been to move before.
+    pub fn reply_frequencies(&self, pos: &ChessState) -> Vec<(Move, Vec<(Move, f32)>)> {
+        let mut replies = Vec::new();
+
+        if self.moves.is_empty() {
+            return replies;
+        }
+
+        pos.map_legal_moves(|mov| {
+            let mut child = pos.clone();
+            child.make_move(mov);
+
+            let frequencies = self.frequencies(child.hash());
+
+            if !frequencies.is_empty() {
+                replies.push((mov, frequencies));
+            }
+        });
+
+        replies
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+
+        for (hash, moves) in &self.moves {
+            for &(mov, count) in moves {
+                writeln!(file, "{hash:016x} {} {count}", u16::from(mov))?;
+            }
+        }
+
+        file.flush()
+    }
+}
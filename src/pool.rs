@@ -0,0 +1,225 @@
+//! A global pool of worker threads for maintenance work (zeroing and
+//! clearing the tree and hash table), so that fresh threads don't have
+//! to be spawned each time, e.g. right before a time-critical search.
+//!
+//! The pool starts with no workers, and is sized with [`resize`] by the
+//! `Threads` option. Work split across more threads than there are workers
+//! starts the missing ones first, so that callers that never set `Threads`
+//! (e.g. `bench` and datagen) still get the parallelism they ask for.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Condvar, Mutex, OnceLock,
+    },
+    thread,
+    time::Instant,
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+enum Message {
+    Run(Job, Instant),
+    Exit,
+}
+
+struct Pool {
+    sender: Mutex<mpsc::Sender<Message>>,
+    receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
+    workers: Mutex<usize>,
+}
+
+static POOL: OnceLock<Pool> = OnceLock::new();
+
+static WORKERS: AtomicUsize = AtomicUsize::new(0);
+static JOBS: AtomicUsize = AtomicUsize::new(0);
+static QUEUE_WAIT_MICROS: AtomicU64 = AtomicU64::new(0);
+
+fn pool() -> &'static Pool {
+    POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+
+        Pool {
+            sender: Mutex::new(sender),
+            receiver: Arc::new(Mutex::new(receiver)),
+            workers: Mutex::new(0),
+        }
+    })
+}
+
+/// Queueing metrics of the pool since startup.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolStats {
+    pub workers: usize,
+    pub jobs: usize,
+    /// Total time jobs spent waiting for a free worker.
+    pub queue_wait_micros: u64,
+}
+
+pub fn stats() -> PoolStats {
+    PoolStats {
+        workers: WORKERS.load(Ordering::Relaxed),
+        jobs: JOBS.load(Ordering::Relaxed),
+        queue_wait_micros: QUEUE_WAIT_MICROS.load(Ordering::Relaxed),
+    }
+}
+
+/// Number of threads that maintenance work is split across: the requested
+/// count, capped to the hardware parallelism so that a large `Threads`
+/// value on a shared host doesn't run more than that at once.
+pub fn maintenance_threads(requested: usize) -> usize {
+    let available = thread::available_parallelism().map_or(1, |n| n.get());
+    requested.clamp(1, available)
+}
+
+/// Start or stop workers so that there are `maintenance_threads(threads)`.
+pub fn resize(threads: usize) {
+    set_workers(maintenance_threads(threads), true);
+}
+
+/// Start workers so that there are at least `target`.
+fn grow(target: usize) {
+    if WORKERS.load(Ordering::Relaxed) < target {
+        set_workers(target, false);
+    }
+}
+
+fn set_workers(target: usize, shrink: bool) {
+    let pool = pool();
+    let mut workers = pool.workers.lock().unwrap();
+
+    while *workers < target {
+        let receiver = pool.receiver.clone();
+        thread::spawn(move || worker(&receiver));
+        *workers += 1;
+    }
+
+    while shrink && *workers > target {
+        pool.sender.lock().unwrap().send(Message::Exit).unwrap();
+        *workers -= 1;
+    }
+
+    WORKERS.store(*workers, Ordering::Relaxed);
+}
+
+fn worker(receiver: &Mutex<mpsc::Receiver<Message>>) {
+    loop {
+        let message = receiver.lock().unwrap().recv();
+
+        match message {
+            Ok(Message::Run(job, queued)) => {
+                let wait = queued.elapsed().as_micros() as u64;
+                QUEUE_WAIT_MICROS.fetch_add(wait, Ordering::Relaxed);
+                job();
+            }
+            Ok(Message::Exit) | Err(_) => return,
+        }
+    }
+}
+
+/// Counts down the jobs of a `run_scoped` call.
+struct Latch {
+    remaining: Mutex<usize>,
+    done: Condvar,
+    panicked: AtomicBool,
+}
+
+/// Run all of `jobs` on the pool, returning once they have all finished.
+fn run_scoped<'a>(jobs: Vec<Box<dyn FnOnce() + Send + 'a>>) {
+    if WORKERS.load(Ordering::Relaxed) == 0 || jobs.len() < 2 {
+        for job in jobs {
+            job();
+        }
+
+        return;
+    }
+
+    let latch = Arc::new(Latch {
+        remaining: Mutex::new(jobs.len()),
+        done: Condvar::new(),
+        panicked: AtomicBool::new(false),
+    });
+
+    let sender = pool().sender.lock().unwrap();
+
+    for job in jobs {
+        let latch = latch.clone();
+
+        let job: Box<dyn FnOnce() + Send + 'a> = Box::new(move || {
+            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                latch.panicked.store(true, Ordering::Relaxed);
+            }
+
+            let mut remaining = latch.remaining.lock().unwrap();
+            *remaining -= 1;
+
+            if *remaining == 0 {
+                latch.done.notify_one();
+            }
+        });
+
+        // SAFETY: we wait below for every job to finish before returning,
+        // so nothing borrowed by a job is dropped while it can still run
+        let job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'a>, Job>(job) };
+
+        JOBS.fetch_add(1, Ordering::Relaxed);
+        sender.send(Message::Run(job, Instant::now())).unwrap();
+    }
+
+    drop(sender);
+
+    let mut remaining = latch.remaining.lock().unwrap();
+    while *remaining > 0 {
+        remaining = latch.done.wait(remaining).unwrap();
+    }
+
+    assert!(
+        !latch.panicked.load(Ordering::Relaxed),
+        "maintenance job panicked"
+    );
+}
+
+/// Run `f` on `maintenance_threads(threads)` chunks of `items` in parallel.
+pub fn for_each_chunk<T: Sync, F: Fn(&[T]) + Sync>(items: &[T], threads: usize, f: F) {
+    let threads = maintenance_threads(threads);
+
+    if threads == 1 || items.len() < 2 {
+        f(items);
+        return;
+    }
+
+    grow(threads);
+
+    let chunk_size = items.len().div_ceil(threads);
+    let f = &f;
+
+    run_scoped(
+        items
+            .chunks(chunk_size)
+            .map(|chunk| Box::new(move || f(chunk)) as Box<dyn FnOnce() + Send + '_>)
+            .collect(),
+    );
+}
+
+/// Mutable version of `for_each_chunk`.
+pub fn for_each_chunk_mut<T: Send, F: Fn(&mut [T]) + Sync>(items: &mut [T], threads: usize, f: F) {
+    let threads = maintenance_threads(threads);
+
+    if threads == 1 || items.len() < 2 {
+        f(items);
+        return;
+    }
+
+    grow(threads);
+
+    let chunk_size = items.len().div_ceil(threads);
+    let f = &f;
+
+    run_scoped(
+        items
+            .chunks_mut(chunk_size)
+            .map(|chunk| Box::new(move || f(chunk)) as Box<dyn FnOnce() + Send + '_>)
+            .collect(),
+    );
+}
@@ -0,0 +1,163 @@
+//! Timings of the phases of network evaluation, collected with the
+//! `profile` feature and summarised by the `profile` command.
+//!
+//! Without the feature `time` only runs the closure, so release builds
+//! are unaffected.
+
+#[derive(Clone, Copy)]
+pub enum Phase {
+    /// Threat features, PST and first layer of the value network.
+    ValueFeatures,
+    /// Remaining layers of the value network.
+    ValueLayers,
+    /// Hidden layer of the policy network, once per expanded node.
+    PolicyHidden,
+    /// Policy output for each move of an expanded node.
+    PolicyOutput,
+}
+
+impl Phase {
+    #[cfg(feature = "profile")]
+    const ALL: [Phase; 4] = [
+        Phase::ValueFeatures,
+        Phase::ValueLayers,
+        Phase::PolicyHidden,
+        Phase::PolicyOutput,
+    ];
+
+    #[cfg(feature = "profile")]
+    fn name(self) -> &'static str {
+        match self {
+            Phase::ValueFeatures => "value features",
+            Phase::ValueLayers => "value layers",
+            Phase::PolicyHidden => "policy hidden",
+            Phase::PolicyOutput => "policy output",
+        }
+    }
+}
+
+#[cfg(not(feature = "profile"))]
+#[inline(always)]
+pub fn time<T>(_phase: Phase, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(not(feature = "profile"))]
+pub fn print_summary() {
+    println!("info string built without the profile feature");
+}
+
+#[cfg(not(feature = "profile"))]
+pub fn reset() {}
+
+#[cfg(feature = "profile")]
+pub use enabled::{print_summary, reset, time};
+
+#[cfg(feature = "profile")]
+mod enabled {
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        time::Instant,
+    };
+
+    use super::Phase;
+
+    /// Total nanoseconds and calls of each phase on one thread. Only the
+    /// owning thread writes to them, so the atomics are uncontended.
+    struct Counters([(AtomicU64, AtomicU64); Phase::ALL.len()]);
+
+    impl Counters {
+        const fn new() -> Self {
+            Self([const { (AtomicU64::new(0), AtomicU64::new(0)) }; Phase::ALL.len()])
+        }
+
+        fn add(&self, other: &Counters) {
+            for ((total, calls), (t, c)) in self.0.iter().zip(&other.0) {
+                total.fetch_add(t.load(Ordering::Relaxed), Ordering::Relaxed);
+                calls.fetch_add(c.load(Ordering::Relaxed), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Counters of every live thread that has timed something.
+    static THREADS: Mutex<Vec<Arc<Counters>>> = Mutex::new(Vec::new());
+
+    /// Counts of threads that have exited, so that short-lived threads
+    /// (one per search without a pool) don't pile up in `THREADS`.
+    static EXITED: Counters = Counters::new();
+
+    /// A thread's entry in `THREADS`, folded into `EXITED` when it exits.
+    struct Registered(Arc<Counters>);
+
+    impl Registered {
+        fn new() -> Self {
+            let counters = Arc::new(Counters::new());
+            THREADS.lock().unwrap().push(counters.clone());
+            Self(counters)
+        }
+    }
+
+    impl Drop for Registered {
+        fn drop(&mut self) {
+            let mut threads = THREADS.lock().unwrap();
+            threads.retain(|counters| !Arc::ptr_eq(counters, &self.0));
+
+            // under the lock, so a summary never sees the counts twice
+            EXITED.add(&self.0);
+        }
+    }
+
+    thread_local! {
+        static COUNTERS: Registered = Registered::new();
+    }
+
+    pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        let nanos = start.elapsed().as_nanos() as u64;
+
+        COUNTERS.with(|Registered(counters)| {
+            let (total, calls) = &counters.0[phase as usize];
+            total.fetch_add(nanos, Ordering::Relaxed);
+            calls.fetch_add(1, Ordering::Relaxed);
+        });
+
+        res
+    }
+
+    pub fn print_summary() {
+        let threads = THREADS.lock().unwrap();
+
+        for phase in Phase::ALL {
+            let (mut total, mut calls) = (0, 0);
+
+            for counters in threads.iter().map(|c| &**c).chain([&EXITED]) {
+                let (t, c) = &counters.0[phase as usize];
+                total += t.load(Ordering::Relaxed);
+                calls += c.load(Ordering::Relaxed);
+            }
+
+            println!(
+                "info string profile {:<14} total {:>8} ms calls {:>10} avg {:>8} ns",
+                phase.name(),
+                total / 1_000_000,
+                calls,
+                total.checked_div(calls).unwrap_or(0),
+            );
+        }
+    }
+
+    pub fn reset() {
+        let threads = THREADS.lock().unwrap();
+
+        for counters in threads.iter().map(|c| &**c).chain([&EXITED]) {
+            for (total, calls) in &counters.0 {
+                total.store(0, Ordering::Relaxed);
+                calls.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Quantisation of a trained policy network (`quantise --in <file>
+//! --out <file>`), from the raw `f32` memory image written by the trainer
+//! into the `i8` network loaded by the engine.
+//!
+//! The quantised network is checked against the original on every legal
+//! move of the bench positions (or the FENs of `--fens <file>`), as a
+//! badly clipped or overflowing weight shows up as a gap in the policy.
+//!
+//! `QA` and `QB` are not options, as inference divides by the same
+//! constants, so a network quantised with any others would be misread.
+
+use std::{fs, io::Write};
+
+use crate::{
+    chess::ChessState,
+    networks::{checksum, PolicyNetwork, UnquantisedPolicyNetwork},
+    read_into_struct_unchecked, uci, MappedWeights,
+};
+
+/// `quantise --in <file> [--out <file>] [--fens <file>]`, written by
+/// default to the `nn-<sha>.network` name of the quantised network.
+pub fn run_with_args(args: impl Iterator<Item = String>) {
+    let mut input = None;
+    let mut output = None;
+    let mut fens = None;
+
+    let mut args = args;
+
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--in", Some(value)) => input = Some(value),
+            ("--out", Some(value)) => output = Some(value),
+            ("--fens", Some(value)) => fens = Some(value),
+            _ => println!("info string unrecognised argument {arg}"),
+        }
+    }
+
+    let Some(input) = input else {
+        println!("info string quantise needs --in <file>");
+        return;
+    };
+
+    let fens: Vec<String> = match fens {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(str::to_string).collect(),
+            Err(err) => {
+                println!("info string failed to read {path}: {err}");
+                return;
+            }
+        },
+        None => uci::BENCH_FENS.iter().map(|fen| fen.to_string()).collect(),
+    };
+
+    let unquantised: MappedWeights<UnquantisedPolicyNetwork> =
+        unsafe { read_into_struct_unchecked(&input) };
+
+    let quantised = unquantised.data.quantise();
+
+    let bytes = unsafe {
+        let ptr: *const PolicyNetwork = quantised.as_ref();
+        std::slice::from_raw_parts(ptr.cast::<u8>(), std::mem::size_of::<PolicyNetwork>())
+    };
+
+    let output = output.unwrap_or_else(|| checksum::file_name(bytes));
+
+    if let Err(err) = fs::File::create(&output).and_then(|mut file| file.write_all(bytes)) {
+        println!("info string failed to write {output}: {err}");
+        return;
+    }
+
+    println!("info string wrote {output}");
+
+    verify(unquantised.data, &quantised, &fens);
+}
+
+/// Print how far the policy of `quantised` is from that of `unquantised`.
+fn verify(unquantised: &UnquantisedPolicyNetwork, quantised: &PolicyNetwork, fens: &[String]) {
+    let (mut positions, mut moves) = (0, 0);
+    let (mut agree, mut total_err, mut max_err) = (0, 0.0, 0.0f32);
+
+    for fen in fens
+        .iter()
+        .map(|fen| fen.trim())
+        .filter(|fen| !fen.is_empty())
+    {
+        let pos = ChessState::from_fen(fen);
+        let board = pos.board();
+
+        let float_hl = unquantised.hl(&board);
+        let quant_hl = quantised.hl(&board);
+
+        let mut logits = Vec::new();
+
+        pos.map_legal_moves(|mov| {
+            logits.push((
+                unquantised.get(&board, &mov, &float_hl),
+                quantised.get(&board, &mov, &quant_hl),
+            ));
+        });
+
+        if logits.is_empty() {
+            continue;
+        }
+
+        let float_policy = softmax(logits.iter().map(|l| l.0));
+        let quant_policy = softmax(logits.iter().map(|l| l.1));
+
+        for (&p, &q) in float_policy.iter().zip(quant_policy.iter()) {
+            let err = (p - q).abs();
+            total_err += err;
+            max_err = max_err.max(err);
+        }
+
+        if argmax(&float_policy) == argmax(&quant_policy) {
+            agree += 1;
+        }
+
+        positions += 1;
+        moves += logits.len();
+    }
+
+    println!(
+        "info string verified {positions} positions {moves} moves best move agreement {:.2}% \
+         policy error avg {:.6} max {:.6}",
+        100.0 * agree as f32 / positions.max(1) as f32,
+        total_err / moves.max(1) as f32,
+        max_err,
+    );
+}
+
+fn softmax(logits: impl Iterator<Item = f32> + Clone) -> Vec<f32> {
+    let max = logits.clone().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.map(|l| (l - max).exp()).collect();
+    let total: f32 = exps.iter().sum();
+
+    exps.into_iter().map(|e| e / total).collect()
+}
+
+fn argmax(policy: &[f32]) -> usize {
+    (0..policy.len())
+        .max_by(|&a, &b| policy[a].total_cmp(&policy[b]))
+        .unwrap_or(0)
+}
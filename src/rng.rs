@@ -0,0 +1,39 @@
+//! Xorshift generator for the engine's few uses of randomness (move
+//! sampling, Gumbel noise, self-tuning), none of which need a better one.
+
+use std::{
+    hash::{BuildHasher, RandomState},
+    time::Instant,
+};
+
+pub struct Rng(u64);
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rng {
+    /// Seeded from the OS, through the standard library's hasher keys.
+    pub fn new() -> Self {
+        Self::with_seed(RandomState::new().hash_one(Instant::now()))
+    }
+
+    /// Seeded with `seed`, for sequences that must be reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in `(0, 1)`, from the top 24 bits.
+    pub fn next_f32(&mut self) -> f32 {
+        ((self.next_u64() >> 40) as f32 + 0.5) / (1u64 << 24) as f32
+    }
+}
@@ -0,0 +1,128 @@
+//! Conversions between the different ways a score is expressed:
+//! - Q, an expected score in [0, 1] from the side to move's perspective
+//! - the internal centipawn scale used by the value network
+//! - centipawns as reported over UCI
+//! - WDL triples, as output by the value network
+//! - mate distances, as proven by the search
+
+use crate::chess::GameState;
+
+/// Scale of the logistic relating internal centipawns to Q. The value
+/// network's output is read at the `eval_scale` param instead.
+pub const EVAL_SCALE: f32 = 400.0;
+
+/// Default Q reported as 100 centipawns, see `SearchOptions::cp_100_q`.
+pub const DEFAULT_CP_100_Q: f32 = 0.607;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Wdl {
+    pub win: f32,
+    pub draw: f32,
+    pub loss: f32,
+}
+
+impl Wdl {
+    pub fn q(&self) -> f32 {
+        self.win + self.draw / 2.0
+    }
+
+    pub fn flip(self) -> Self {
+        Self {
+            win: self.loss,
+            draw: self.draw,
+            loss: self.win,
+        }
+    }
+}
+
+/// Internal centipawns corresponding to a Q.
+pub fn q_to_eval_cp(q: f32, scale: f32) -> i32 {
+    (-scale * (1.0 / q.clamp(0.0, 1.0) - 1.0).ln()) as i32
+}
+
+/// Q corresponding to internal centipawns.
+pub fn eval_cp_to_q(cp: i32, scale: f32) -> f32 {
+    1.0 / (1.0 + (-(cp as f32) / scale).exp())
+}
+
+/// Centipawns reported over UCI for a Q: a logistic of its deviation from
+/// 0.5 cubed up to `cp_100_q`, and linear beyond, with the same slope.
+pub fn q_to_cp(q: f32, cp_100_q: f32) -> f32 {
+    // the logistic gives 100 cp here
+    const ADJUSTED_100: f32 = 0.622_459_3;
+
+    let threshold = cp_100_q - 0.5;
+    let clamped_score = q.clamp(0.0, 1.0);
+    let deviation = (clamped_score - 0.5).abs();
+    let sign = (clamped_score - 0.5).signum();
+    if deviation > threshold {
+        let slope =
+            600.0 * (ADJUSTED_100 - 0.5) / (threshold * ADJUSTED_100 * (1.0 - ADJUSTED_100));
+        (100.0 + slope * (deviation - threshold)) * sign
+    } else {
+        let cube_scale = (ADJUSTED_100 - 0.5) / threshold.powi(3);
+        let adjusted_score = 0.5 + (clamped_score - 0.5).powi(3) * cube_scale;
+        -200.0 * (1.0 / adjusted_score - 1.0).ln()
+    }
+}
+
+/// A score as reported to the user.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Score {
+    /// Expected score from the side to move's perspective.
+    Q(f32),
+    /// Full moves until mate, negative if the side to move is getting mated.
+    Mate(i32),
+}
+
+impl Score {
+    /// Score of a move, given the game state of the child it leads to (from
+    /// the opponent's perspective) and the child's Q (as stored in the tree,
+    /// from the side to move's perspective).
+    pub fn from_child(state: GameState, q: f32) -> Self {
+        match state {
+            GameState::Lost(n) => Self::Mate(i32::from(n) / 2 + 1),
+            GameState::Won(n) => Self::Mate(-(i32::from(n) + 1) / 2),
+            GameState::Draw => Self::Q(0.5),
+            GameState::Ongoing => Self::Q(q),
+        }
+    }
+
+    pub fn q(self) -> f32 {
+        match self {
+            Self::Q(q) => q,
+            Self::Mate(n) => f32::from(u8::from(n > 0)),
+        }
+    }
+
+    /// Centipawns at the default normalisation, for comparing scores
+    /// internally (e.g. in time management).
+    pub fn cp(self) -> f32 {
+        self.cp_with(DEFAULT_CP_100_Q)
+    }
+
+    /// Centipawns as reported, with `cp_100_q` the Q reported as 100 cp.
+    pub fn cp_with(self, cp_100_q: f32) -> f32 {
+        q_to_cp(self.q(), cp_100_q)
+    }
+
+    pub fn is_mate(self) -> bool {
+        matches!(self, Self::Mate(_))
+    }
+
+    /// `{"cp": .., "q": ..}` or `{"mate": ..}`.
+    pub fn to_json(self, cp_100_q: f32) -> String {
+        match self {
+            Self::Q(q) => format!("{{\"cp\":{:.0},\"q\":{q:.4}}}", q_to_cp(q, cp_100_q)),
+            Self::Mate(n) => format!("{{\"mate\":{n}}}"),
+        }
+    }
+
+    /// `cp ..` or `mate ..`, as in a UCI `info` line.
+    pub fn to_uci(self, cp_100_q: f32) -> String {
+        match self {
+            Self::Q(q) => format!("cp {:.0}", q_to_cp(q, cp_100_q)),
+            Self::Mate(n) => format!("mate {n}"),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! SPSA tuning of `MctsParams` by self-play (`selftune`), for tuning
+//! without an OpenBench instance.
+//!
+//! Each iteration plays a pair of games (one with each colour) from a
+//! random opening between the current params perturbed up and down, and
+//! moves the params towards the winner, using the step sizes and learning
+//! rates of `MctsParams::list_spsa` with the schedule of OpenBench.
+//! The params are written to a TOML file after every iteration.
+
+use std::{fs, time::Instant};
+
+use crate::{
+    chess::{ChessState, GameState},
+    engine::Engine,
+    mcts::{Limits, MctsParams, SearchOptions, Tunable},
+    networks::{PolicyNetwork, ValueNetwork},
+    openings,
+    rng::Rng,
+};
+
+/// Games are adjudicated as draws after this many plies.
+const MAX_PLIES: usize = 400;
+
+struct Options {
+    /// Names of the params to tune, or empty for all those that matter at
+    /// a fixed node count (i.e. not the `tm_` ones).
+    params: Vec<String>,
+    iterations: usize,
+    pairs: usize,
+    nodes: usize,
+    output: String,
+}
+
+/// `selftune [--params <a,b,..>] [--iterations <n>] [--pairs <n>]
+/// [--nodes <n>] [--output <file>]`
+pub fn run_with_args(
+    args: impl Iterator<Item = String>,
+    policy: &PolicyNetwork,
+    value: &ValueNetwork,
+) {
+    let mut opts = Options {
+        params: Vec::new(),
+        iterations: 1000,
+        pairs: 1,
+        nodes: 1000,
+        output: "selftune.toml".to_string(),
+    };
+
+    let mut args = args;
+
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--params", Some(value)) => {
+                opts.params = value.split(',').map(str::to_string).collect();
+            }
+            ("--iterations", Some(value)) => {
+                opts.iterations = value.parse().unwrap_or(opts.iterations);
+            }
+            ("--pairs", Some(value)) => opts.pairs = value.parse().unwrap_or(opts.pairs).max(1),
+            ("--nodes", Some(value)) => opts.nodes = value.parse().unwrap_or(opts.nodes),
+            ("--output", Some(value)) => opts.output = value,
+            _ => println!("info string unrecognised argument {arg}"),
+        }
+    }
+
+    run(&opts, policy, value);
+}
+
+fn run(opts: &Options, policy: &PolicyNetwork, value: &ValueNetwork) {
+    let mut params = MctsParams::default();
+
+    let tunables = params
+        .tunables()
+        .into_iter()
+        .filter(|tunable| {
+            if opts.params.is_empty() {
+                !tunable.name.starts_with("tm_")
+            } else {
+                opts.params.iter().any(|name| name == tunable.name)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if tunables.is_empty() {
+        println!("info string no params to tune");
+        return;
+    }
+
+    let mut theta = tunables.iter().map(|t| t.value).collect::<Vec<_>>();
+    let mut rng = Rng::new();
+    let timer = Instant::now();
+
+    let mut plus = Engine::new(policy, value, SearchOptions::default());
+    let mut minus = Engine::new(policy, value, SearchOptions::default());
+
+    for k in 1..=opts.iterations {
+        let delta = tunables
+            .iter()
+            .map(|_| if rng.next_u64() & 1 == 0 { 1.0 } else { -1.0 })
+            .collect::<Vec<_>>();
+
+        let schedule = tunables
+            .iter()
+            .map(|tunable| Schedule::new(tunable, k, opts.iterations))
+            .collect::<Vec<_>>();
+
+        for (i, tunable) in tunables.iter().enumerate() {
+            let shift = schedule[i].c * delta[i];
+            set(plus.params_mut(), tunable, theta[i] + shift);
+            set(minus.params_mut(), tunable, theta[i] - shift);
+        }
+
+        let mut result = 0.0;
+
+        for _ in 0..opts.pairs {
+            let opening = openings::random_opening(8, |n| rng.next_u64() as usize % n);
+            result += play_game(&mut plus, &mut minus, &opening, opts.nodes);
+            result -= play_game(&mut minus, &mut plus, &opening, opts.nodes);
+        }
+
+        for (i, tunable) in tunables.iter().enumerate() {
+            let step = schedule[i].r * schedule[i].c * result * delta[i];
+            theta[i] = (theta[i] + step).clamp(tunable.min, tunable.max);
+            set(&mut params, tunable, theta[i]);
+        }
+
+        println!(
+            "info string iteration {k} of {} result {result} time {}s",
+            opts.iterations,
+            timer.elapsed().as_secs()
+        );
+
+        if let Err(err) = fs::write(&opts.output, params.to_toml()) {
+            println!("info string failed to write {}: {err}", opts.output);
+            return;
+        }
+    }
+
+    for (i, tunable) in tunables.iter().enumerate() {
+        println!(
+            "info string {} {:.1} -> {:.1}",
+            tunable.name, tunable.value, theta[i]
+        );
+    }
+}
+
+/// Perturbation `c` and learning rate `r` of a param in iteration `k` of
+/// `total`, as in OpenBench.
+struct Schedule {
+    c: f64,
+    r: f64,
+}
+
+impl Schedule {
+    fn new(tunable: &Tunable, k: usize, total: usize) -> Self {
+        const ALPHA: f64 = 0.602;
+        const GAMMA: f64 = 0.101;
+
+        let (k, total) = (k as f64, total as f64);
+        let big_a = 0.1 * total;
+
+        let c_end = tunable.step;
+        let a_end = tunable.r * c_end.powi(2);
+        let a = a_end * (big_a + total).powf(ALPHA);
+
+        let c = c_end * total.powf(GAMMA) / k.powf(GAMMA);
+        let a_k = a / (big_a + k).powf(ALPHA);
+
+        Self {
+            c,
+            r: a_k / c.powi(2),
+        }
+    }
+}
+
+fn set(params: &mut MctsParams, tunable: &Tunable, value: f64) {
+    params.set(
+        tunable.name,
+        value.clamp(tunable.min, tunable.max).round() as i32,
+    );
+}
+
+/// Score of `white` against `black` from `pos`: 1 for a win, 0 for a draw
+/// and -1 for a loss.
+fn play_game<'a>(
+    white: &mut Engine<'a>,
+    black: &mut Engine<'a>,
+    pos: &ChessState,
+    nodes: usize,
+) -> f64 {
+    white.clear();
+    black.clear();
+
+    let mut pos = pos.clone();
+
+    for _ in 0..MAX_PLIES {
+        let engine = if pos.stm() == 0 {
+            &mut *white
+        } else {
+            &mut *black
+        };
+
+        let result = engine.think(&pos, Limits::nodes(nodes));
+        pos.make_move(result.best_move);
+
+        // `Lost` is from the perspective of the side to move
+        match pos.game_state() {
+            GameState::Ongoing => {}
+            GameState::Lost(_) if pos.stm() == 1 => return 1.0,
+            GameState::Lost(_) => return -1.0,
+            _ => return 0.0,
+        }
+    }
+
+    0.0
+}
@@ -0,0 +1,222 @@
+//! Analysis server (`serve --port <n>`), for hosting the engine behind a
+//! website or bot.
+//!
+//! Requests are lines sent over TCP, of the form
+//! `fen <fen> [moves <moves>] [nodes <n>] [movetime <ms>]`, with `startpos`
+//! allowed in place of `fen <fen>`. Each is answered with a JSON object per
+//! line for every `SLICE_MS` of search, then one with the `bestmove`.
+//! Clients are served one at a time, all sharing one tree.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    time::Instant,
+};
+
+use crate::{
+    chess::ChessState,
+    engine::Engine,
+    mcts::{Limits, SearchOptions, SearchResult},
+    networks::{PolicyNetwork, ValueNetwork},
+};
+
+/// Interval between progress reports, in ms.
+const SLICE_MS: u128 = 500;
+
+struct Request {
+    pos: ChessState,
+    max_nodes: Option<usize>,
+    movetime: Option<u128>,
+}
+
+impl Request {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut fen = None;
+        let mut moves = Vec::new();
+        let mut max_nodes = None;
+        let mut movetime = None;
+        let mut mode = "";
+
+        for token in line.split_whitespace() {
+            match token {
+                "startpos" => fen = Some(ChessState::STARTPOS.to_string()),
+                "fen" => {
+                    fen = Some(String::new());
+                    mode = "fen";
+                }
+                "moves" | "nodes" | "movetime" => mode = token,
+                _ => match mode {
+                    "fen" => {
+                        let fen = fen.get_or_insert_with(String::new);
+                        fen.push_str(token);
+                        fen.push(' ');
+                    }
+                    "moves" => moves.push(token),
+                    "nodes" => max_nodes = Some(token.parse().map_err(|_| "invalid nodes")?),
+                    "movetime" => movetime = Some(token.parse().map_err(|_| "invalid movetime")?),
+                    _ => return Err(format!("unexpected '{token}'")),
+                },
+            }
+        }
+
+        let fen = fen.ok_or("missing position")?;
+        let mut pos = ChessState::try_from_fen(&fen).map_err(|err| err.to_string())?;
+
+        for mov in moves {
+            let mov = pos
+                .parse_uci(mov)
+                .or_else(|| pos.parse_san(mov))
+                .ok_or(format!("illegal move {mov}"))?;
+
+            pos.make_move(mov);
+        }
+
+        let mut has_moves = false;
+        pos.map_legal_moves(|_| has_moves = true);
+
+        if !has_moves {
+            return Err("no legal moves".to_string());
+        }
+
+        // one second by default
+        if max_nodes.is_none() && movetime.is_none() {
+            movetime = Some(1000);
+        }
+
+        Ok(Self {
+            pos,
+            max_nodes,
+            movetime,
+        })
+    }
+}
+
+/// `serve [--host <address>] [--port <n>]`, by default on `127.0.0.1:8080`.
+pub fn run_with_args(
+    args: impl Iterator<Item = String>,
+    policy: &PolicyNetwork,
+    value: &ValueNetwork,
+) {
+    let mut host = "127.0.0.1".to_string();
+    let mut port = 8080;
+    let mut args = args;
+
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--host", Some(value)) => host = value,
+            ("--port", Some(value)) => port = value.parse().unwrap_or(port),
+            _ => println!("info string unrecognised argument {arg}"),
+        }
+    }
+
+    run(&host, port, policy, value);
+}
+
+pub fn run(host: &str, port: u16, policy: &PolicyNetwork, value: &ValueNetwork) {
+    let listener = match TcpListener::bind((host, port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            println!("info string failed to listen on {host}:{port}: {err}");
+            return;
+        }
+    };
+
+    println!("info string serving on {host}:{port}");
+
+    let mut engine = Engine::new(policy, value, SearchOptions::default());
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| serve_client(&mut engine, stream));
+
+        if let Err(err) = result {
+            println!("info string connection closed: {err}");
+        }
+    }
+}
+
+fn serve_client(engine: &mut Engine, stream: TcpStream) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match Request::parse(&line) {
+            Ok(request) => analyse(engine, &request, &mut writer)?,
+            Err(err) => writeln!(writer, "{{\"error\":\"{}\"}}", escape(&err))?,
+        }
+    }
+
+    Ok(())
+}
+
+fn analyse(engine: &mut Engine, request: &Request, writer: &mut impl Write) -> io::Result<()> {
+    let timer = Instant::now();
+    let mut nodes = 0;
+
+    loop {
+        let remaining_time = request.movetime.map_or(SLICE_MS, |time| {
+            time.saturating_sub(timer.elapsed().as_millis())
+        });
+
+        let slice = Limits {
+            max_time: Some(remaining_time.min(SLICE_MS)),
+            max_nodes: request.max_nodes.map_or(i32::MAX as usize, |n| n - nodes),
+            ..Default::default()
+        };
+
+        let result = engine.think(&request.pos, slice);
+        nodes += result.nodes;
+
+        let time = timer.elapsed().as_millis();
+        // a proven result won't change with more search
+        let proven = result
+            .variations(1)
+            .first()
+            .is_some_and(|line| line.score.is_mate());
+
+        let done = result.nodes == 0
+            || proven
+            || request.movetime.is_some_and(|movetime| time >= movetime)
+            || request
+                .max_nodes
+                .is_some_and(|max_nodes| nodes >= max_nodes);
+
+        let report = report(&result, nodes, time, engine.options().cp_100_q);
+
+        if done {
+            let bestmove = request.pos.conv_mov_to_str(result.best_move);
+            writeln!(writer, "{{\"bestmove\":\"{bestmove}\",{report}}}")?;
+            return writer.flush();
+        }
+
+        writeln!(writer, "{{{report}}}")?;
+        writer.flush()?;
+    }
+}
+
+/// `"nodes":..,"time":..,"score":..,"pv":[..]` of the best line so far.
+fn report(result: &SearchResult, nodes: usize, time: u128, cp_100_q: f32) -> String {
+    let (score, pv) = match result.variations(1).pop() {
+        Some(line) => {
+            let moves = line
+                .moves
+                .iter()
+                .map(|mov| format!("\"{}\"", mov.uci))
+                .collect::<Vec<_>>();
+
+            (line.score.to_json(cp_100_q), moves.join(","))
+        }
+        None => ("null".to_string(), String::new()),
+    };
+
+    format!("\"nodes\":{nodes},\"time\":{time},\"score\":{score},\"pv\":[{pv}]")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,128 @@
+//! Deliberately weaker play (`UCI_LimitStrength` and `UCI_Elo`), for
+//! practice against the engine.
+//!
+//! Strength is taken away in three ways, all scaling with the distance of
+//! the target below `ENGINE_ELO`: the time and node limits of the search
+//! are cut, halving about every 200 Elo, so that the cut vanishes at full
+//! strength; the root policy is flattened and the move sampled from the
+//! visits with a temperature, so that play varies; and occasionally a move
+//! other than the best is played outright, as club players' mistakes are.
+//! The figures are rough, not measured ratings.
+
+use crate::{
+    chess::Move,
+    mcts::{Limits, MctsParams, SearchResult},
+    opponent::ENGINE_ELO,
+};
+
+#[derive(Clone, Copy)]
+pub struct Strength {
+    pub limit: bool,
+    pub elo: i32,
+}
+
+impl Default for Strength {
+    fn default() -> Self {
+        Self {
+            limit: false,
+            elo: ENGINE_ELO,
+        }
+    }
+}
+
+impl Strength {
+    pub const MIN_ELO: i32 = 1000;
+
+    /// Fewest playouts a node limit is cut to.
+    const MIN_NODES: usize = 16;
+    /// Playouts taken as a full search when there are no limits to cut
+    /// (e.g. `go infinite`).
+    const FULL_NODES: f64 = 65536.0;
+    const ELO_PER_DOUBLING: f64 = 200.0;
+
+    pub fn set_elo(&mut self, elo: i32) {
+        self.elo = elo.clamp(Self::MIN_ELO, ENGINE_ELO);
+    }
+
+    /// How far below full strength play is, from 0 to 1.
+    fn weakness(&self) -> f32 {
+        if !self.limit {
+            return 0.0;
+        }
+
+        (ENGINE_ELO - self.elo) as f32 / (ENGINE_ELO - Self::MIN_ELO) as f32
+    }
+
+    /// Share of a full search that is made, 1 at full strength.
+    fn search_fraction(&self) -> f64 {
+        if !self.limit {
+            return 1.0;
+        }
+
+        let doublings = f64::from(ENGINE_ELO - self.elo) / Self::ELO_PER_DOUBLING;
+        (-doublings).exp2()
+    }
+
+    /// `limits` cut down to `search_fraction` of the search.
+    pub fn limits(&self, limits: Limits) -> Limits {
+        let fraction = self.search_fraction();
+
+        if fraction >= 1.0 {
+            return limits;
+        }
+
+        let unlimited = Limits::default();
+        let time = |ms: u128| ((ms as f64 * fraction) as u128).max(1);
+        let nodes = |nodes: f64| ((nodes * fraction) as usize).max(Self::MIN_NODES);
+
+        let max_nodes = if limits.max_nodes < unlimited.max_nodes {
+            nodes(limits.max_nodes as f64)
+        } else if limits.max_time.is_none() {
+            nodes(Self::FULL_NODES)
+        } else {
+            limits.max_nodes
+        };
+
+        Limits {
+            max_time: limits.max_time.map(time),
+            opt_time: limits.opt_time.map(time),
+            max_nodes,
+            ..limits
+        }
+    }
+
+    /// `params` with the root policy flattened.
+    pub fn params(&self, params: &MctsParams) -> MctsParams {
+        let mut params = params.clone();
+
+        if self.weakness() == 0.0 {
+            return params;
+        }
+
+        let temperature = params.root_policy_temperature() * (1.0 + 2.0 * self.weakness());
+        params.set("root_policy_temperature", (temperature * 1000.0) as i32);
+        params
+    }
+
+    /// Temperature of the move played, at least `temperature`.
+    pub fn move_temperature(&self, temperature: f32) -> f32 {
+        temperature.max(self.weakness())
+    }
+
+    /// The move to play from `result`, with `uniform` giving random
+    /// numbers in `[0, 1)`.
+    pub fn choose_move(
+        &self,
+        result: &SearchResult,
+        temperature: f32,
+        mut uniform: impl FnMut() -> f32,
+    ) -> Move {
+        let inferior_chance = 0.2 * self.weakness().powi(2);
+
+        if uniform() < inferior_chance {
+            result.sample_inferior_move(uniform())
+        } else {
+            result.sample_move(self.move_temperature(temperature), uniform())
+        }
+    }
+}
@@ -1,20 +1,25 @@
 mod half;
 mod hash;
 mod node;
+mod pages;
+mod serialise;
 
 use half::TreeHalf;
 use hash::{HashEntry, HashTable};
 pub use node::{Node, NodePtr};
+pub use pages::set_enabled as set_large_pages;
 
 use std::{
+    mem,
     sync::atomic::{AtomicBool, Ordering},
     time::Instant,
 };
 
 use crate::{
-    chess::{ChessState, GameState},
-    mcts::{MctsParams, SearchHelpers},
-    networks::PolicyNetwork,
+    chess::{ChessState, GameState, Move, MoveList},
+    log,
+    mcts::{CorrHist, MctsParams, Priors, SearchHelpers},
+    networks::{Accumulator, PolicyNetwork, POLICY_L1},
 };
 
 pub struct Tree {
@@ -22,6 +27,11 @@ pub struct Tree {
     tree: [TreeHalf; 2],
     half: AtomicBool,
     hash: HashTable,
+    root_moves: Vec<Move>,
+    reply_boost: Vec<(Move, Vec<(Move, f32)>)>,
+    corrhist: CorrHist,
+    /// Size given to `new_mb`.
+    mb: usize,
 }
 
 impl std::ops::Index<NodePtr> for Tree {
@@ -33,24 +43,40 @@ impl std::ops::Index<NodePtr> for Tree {
 }
 
 impl Tree {
+    /// Number of tree nodes per hash table entry.
+    const NODES_PER_HASH_ENTRY: usize = 64;
+
+    /// Create a tree whose halves and hash table together take up `mb` MiB.
     pub fn new_mb(mb: usize, threads: usize) -> Self {
         let bytes = mb * 1024 * 1024;
 
-        Self::new(bytes / 48, bytes / 48 / 16, threads)
+        let bytes_per_node = mem::size_of::<Node>() as f64
+            + mem::size_of::<HashEntry>() as f64 / Self::NODES_PER_HASH_ENTRY as f64;
+        let tree_cap = (bytes as f64 / bytes_per_node) as usize;
+
+        Self::new(tree_cap, tree_cap / Self::NODES_PER_HASH_ENTRY, threads, mb)
     }
 
-    fn new(tree_cap: usize, hash_cap: usize, threads: usize) -> Self {
+    fn new(tree_cap: usize, hash_cap: usize, threads: usize, mb: usize) -> Self {
         Self {
             root: ChessState::default(),
             tree: [
-                TreeHalf::new(tree_cap / 2, false, threads),
-                TreeHalf::new(tree_cap / 2, true, threads),
+                TreeHalf::new(tree_cap / 2, false),
+                TreeHalf::new(tree_cap / 2, true),
             ],
             half: AtomicBool::new(false),
-            hash: HashTable::new(hash_cap / 4, threads),
+            hash: HashTable::new(hash_cap, threads),
+            root_moves: Vec::new(),
+            reply_boost: Vec::new(),
+            corrhist: CorrHist::default(),
+            mb,
         }
     }
 
+    pub fn mb(&self) -> usize {
+        self.mb
+    }
+
     pub fn root_position(&self) -> &ChessState {
         &self.root
     }
@@ -72,8 +98,8 @@ impl Tree {
             return Some(());
         }
 
-        let f = &mut *self[from].actions_mut();
-        let t = &mut *self[to].actions_mut();
+        let f = self[from].actions_mut();
+        let t = self[to].actions_mut();
 
         // no other thread is able to modify `from`
         // whilst the above write locks are held,
@@ -81,8 +107,8 @@ impl Tree {
         // (for a thread that calls this function whilst
         // another thread is already doing the same work)
         self[to].copy_from(&self[from]);
+        t.set(f.get());
         self[to].set_num_actions(self[from].num_actions());
-        *t = *f;
 
         Some(())
     }
@@ -112,28 +138,35 @@ impl Tree {
 
     #[must_use]
     pub fn fetch_children(&self, parent_ptr: NodePtr) -> Option<()> {
-        let first_child_ptr = { *self[parent_ptr].actions() };
+        let first_child_ptr = self[parent_ptr].actions();
 
         if first_child_ptr.half() != self.half.load(Ordering::Relaxed) {
-            let mut most_recent_ptr = self[parent_ptr].actions_mut();
+            let most_recent_ptr = self[parent_ptr].actions_mut();
 
-            if most_recent_ptr.half() == self.half.load(Ordering::Relaxed) {
+            if most_recent_ptr.get().half() == self.half.load(Ordering::Relaxed) {
                 return Some(());
             }
 
-            assert_eq!(first_child_ptr, *most_recent_ptr);
+            assert_eq!(first_child_ptr, most_recent_ptr.get());
 
             let num_children = self[parent_ptr].num_actions();
             let new_ptr = self.tree[self.half()].reserve_nodes(num_children)?;
 
             self.copy_across(first_child_ptr, num_children, new_ptr);
 
-            *most_recent_ptr = new_ptr;
+            most_recent_ptr.set(new_ptr);
         }
 
         Some(())
     }
 
+    /// Permille of the current tree half in use, as reported by `hashfull`.
+    pub fn hashfull(&self) -> usize {
+        let half = &self.tree[self.half()];
+        // `used` can overshoot the capacity when a reservation fails
+        (half.used().min(half.capacity()) * 1000) / half.capacity().max(1)
+    }
+
     pub fn root_node(&self) -> NodePtr {
         NodePtr::new(self.half.load(Ordering::Relaxed), 0)
     }
@@ -142,36 +175,64 @@ impl Tree {
         self.hash.get(hash)
     }
 
-    pub fn push_hash(&self, hash: u64, wins: f32) {
-        self.hash.push(hash, wins);
+    pub fn prefetch_hash(&self, hash: u64) {
+        self.hash.prefetch(hash);
     }
 
-    fn clear_halves(&self) {
+    /// Whether `entry` was written by an earlier search than this one.
+    pub fn is_older_hash_entry(&self, entry: HashEntry) -> bool {
+        self.hash.is_older(entry)
+    }
+
+    pub fn push_hash(&self, hash: u64, wins: f32, visits: i32) {
+        self.hash.push(hash, wins, visits);
+    }
+
+    /// Permille of hash entries from the current search, and from older ones.
+    pub fn hash_usage(&self) -> (usize, usize) {
+        self.hash.usage()
+    }
+
+    /// Remove all nodes, but keep the hash table.
+    pub fn clear_halves(&self) {
         self.tree[0].clear();
         self.tree[1].clear();
     }
 
     pub fn clear(&mut self, threads: usize) {
         self.root = ChessState::default();
+        self.root_moves.clear();
+        self.reply_boost.clear();
         self.clear_halves();
         self.hash.clear(threads);
+        self.corrhist.clear();
+    }
+
+    /// Persists between searches, until the tree is cleared.
+    pub fn corrhist(&self) -> &CorrHist {
+        &self.corrhist
     }
 
     pub fn is_empty(&self) -> bool {
         self.tree[0].is_empty() && self.tree[1].is_empty()
     }
 
+    /// Add up to `width` children (those with the highest priors) to a
+    /// node, with the rest added later by `widen_node`.
+    #[allow(clippy::too_many_arguments)]
     pub fn expand_node(
         &self,
         node_ptr: NodePtr,
         pos: &ChessState,
         params: &MctsParams,
+        priors: Priors,
         policy: &PolicyNetwork,
         depth: usize,
+        width: usize,
     ) -> Option<()> {
         let node = &self[node_ptr];
 
-        let mut actions_ptr = node.actions_mut();
+        let actions_ptr = node.actions_mut();
 
         // when running with >1 threads, this function may
         // be called twice, and this acts as a safeguard in
@@ -180,106 +241,286 @@ impl Tree {
             return Some(());
         }
 
-        let feats = pos.get_policy_feats(policy);
-        let mut max = f32::NEG_INFINITY;
-        let mut actions = Vec::new();
+        let (mut actions, gini_impurity) =
+            self.move_priors(node_ptr, pos, params, priors, policy, depth);
 
-        pos.map_legal_moves(|mov| {
-            let policy = pos.get_policy(mov, &feats, policy);
-            actions.push((mov, policy));
-            max = max.max(policy);
-        });
+        let num_moves = actions.len();
+
+        if width < num_moves {
+            actions.sort_by(|a, b| b.1.total_cmp(&a.1));
+            actions.truncate(width.max(1));
+        }
 
         let new_ptr = self.tree[self.half()].reserve_nodes(actions.len())?;
 
-        let pst = SearchHelpers::get_pst(depth, self[node_ptr].q(), params);
+        for (action, &(mov, policy)) in actions.iter().enumerate() {
+            self[new_ptr + action].set_new(mov, policy);
+        }
+
+        node.set_gini_impurity(gini_impurity);
+
+        actions_ptr.set(new_ptr);
+        node.set_num_actions(actions.len());
+        node.set_num_moves(num_moves);
 
-        let mut total = 0.0;
+        Some(())
+    }
 
-        for (_, policy) in actions.iter_mut() {
-            *policy = ((*policy - max) / pst).exp();
-            total += *policy;
+    /// Add children to a partially expanded node, up to `width` in total,
+    /// by moving the existing ones to a larger block.
+    ///
+    /// Playouts already below the old children finish there, so with more
+    /// than one thread a few of their results at the children may be lost.
+    #[allow(clippy::too_many_arguments)]
+    pub fn widen_node(
+        &self,
+        node_ptr: NodePtr,
+        pos: &ChessState,
+        params: &MctsParams,
+        priors: Priors,
+        policy: &PolicyNetwork,
+        depth: usize,
+        width: usize,
+    ) -> Option<()> {
+        let node = &self[node_ptr];
+
+        let actions_ptr = node.actions_mut();
+        let old_width = node.num_actions();
+
+        // another thread may have got here first
+        if width <= old_width || !node.can_widen() {
+            return Some(());
         }
 
-        let mut sum_of_squares = 0.0;
+        let (mut actions, gini_impurity) =
+            self.move_priors(node_ptr, pos, params, priors, policy, depth);
 
-        for (action, &(mov, policy)) in actions.iter().enumerate() {
-            let ptr = new_ptr + action;
-            let policy = policy / total;
+        let mut existing = MoveList::default();
+
+        for action in 0..old_width {
+            existing.push(self[actions_ptr.get() + action].parent_move());
+        }
+
+        actions.retain(|(mov, _)| !existing.contains(mov));
+        actions.sort_by(|a, b| b.1.total_cmp(&a.1));
+        actions.truncate(width - old_width);
 
-            self[ptr].set_new(mov, policy);
-            sum_of_squares += policy * policy;
+        let new_ptr = self.tree[self.half()].reserve_nodes(old_width + actions.len())?;
+
+        self.copy_across(actions_ptr.get(), old_width, new_ptr)?;
+
+        for action in 0..old_width {
+            self[new_ptr + action].clear_threads();
+        }
+
+        for (action, &(mov, policy)) in actions.iter().enumerate() {
+            self[new_ptr + old_width + action].set_new(mov, policy);
         }
 
-        let gini_impurity = (1.0 - sum_of_squares).clamp(0.0, 1.0);
         node.set_gini_impurity(gini_impurity);
 
-        *actions_ptr = new_ptr;
-        node.set_num_actions(actions.len());
+        actions_ptr.set(new_ptr);
+        node.set_num_actions(old_width + actions.len());
 
         Some(())
     }
 
+    /// Normalised priors of the legal moves of a node (restricted by
+    /// `searchmoves` at the root), and their gini impurity.
+    fn move_priors(
+        &self,
+        node_ptr: NodePtr,
+        pos: &ChessState,
+        params: &MctsParams,
+        priors: Priors,
+        policy: &PolicyNetwork,
+        depth: usize,
+    ) -> (MoveList<(Move, f32)>, f32) {
+        let feats = Self::get_prior_feats(pos, priors, policy);
+        let mut actions = MoveList::default();
+
+        let restricted = node_ptr == self.root_node() && self.is_restricted(pos);
+
+        pos.map_legal_moves(|mov| {
+            if restricted && !self.root_moves.contains(&mov) {
+                return;
+            }
+
+            let policy = Self::get_prior(pos, mov, priors, &feats, policy);
+            actions.push((mov, policy));
+        });
+
+        // only reachable from illegal positions (legal ones have at most
+        // 218 moves), in which case the moves with the lowest priors are dropped
+        if actions.len() > Node::MAX_ACTIONS {
+            actions.sort_by(|a, b| b.1.total_cmp(&a.1));
+            actions.truncate(Node::MAX_ACTIONS);
+        }
+
+        let pst = self.policy_temperature(node_ptr, depth, params);
+        let boost = self.get_boost(node_ptr, depth);
+        let gini_impurity = Self::normalise_priors(pos, params, priors, pst, boost, &mut actions);
+
+        (actions, gini_impurity)
+    }
+
     pub fn relabel_policy(
         &self,
         node_ptr: NodePtr,
         pos: &ChessState,
         params: &MctsParams,
+        priors: Priors,
         policy: &PolicyNetwork,
         depth: u8,
     ) {
-        let feats = pos.get_policy_feats(policy);
-        let mut max = f32::NEG_INFINITY;
+        let feats = Self::get_prior_feats(pos, priors, policy);
 
-        let mut policies = Vec::new();
+        let mut policies = MoveList::default();
 
         let actions = self[node_ptr].actions_mut();
         let num_actions = self[node_ptr].num_actions();
 
         for action in 0..num_actions {
-            let mov = self[*actions + action].parent_move();
-            let policy = pos.get_policy(mov, &feats, policy);
+            let mov = self[actions.get() + action].parent_move();
+            let policy = Self::get_prior(pos, mov, priors, &feats, policy);
 
-            policies.push(policy);
-            max = max.max(policy);
+            policies.push((mov, policy));
         }
 
-        let pst = SearchHelpers::get_pst(depth.into(), self[node_ptr].q(), params);
+        let pst = self.policy_temperature(node_ptr, depth.into(), params);
+        let boost = self.get_boost(node_ptr, depth.into());
+        let gini_impurity = Self::normalise_priors(pos, params, priors, pst, boost, &mut policies);
 
-        let mut total = 0.0;
+        for (action, &(_, policy)) in policies.iter().enumerate() {
+            self[actions.get() + action].set_policy(policy);
+        }
 
-        for policy in &mut policies {
-            *policy = ((*policy - max) / pst).exp();
-            total += *policy;
+        self[node_ptr].set_gini_impurity(gini_impurity);
+    }
+
+    /// Softmax temperature of a node's priors: the depth PST, scaled by
+    /// `root_policy_temperature` at the root and `policy_temperature`
+    /// elsewhere.
+    fn policy_temperature(&self, node_ptr: NodePtr, depth: usize, params: &MctsParams) -> f32 {
+        let scale = if node_ptr == self.root_node() {
+            params.root_policy_temperature()
+        } else {
+            params.policy_temperature()
+        };
+
+        scale * SearchHelpers::get_pst(depth, self[node_ptr].q(), params)
+    }
+
+    /// Priors to blend into those of a node, only set at the children of
+    /// the root (depth 2), where the opponent is to move.
+    fn get_boost(&self, node_ptr: NodePtr, depth: usize) -> &[(Move, f32)] {
+        if depth != 2 {
+            return &[];
         }
 
-        let mut sum_of_squares = 0.0;
+        let mov = self[node_ptr].parent_move();
 
-        for (action, &policy) in policies.iter().enumerate() {
-            let policy = policy / total;
-            self[*actions + action].set_policy(policy);
-            sum_of_squares += policy * policy;
+        self.reply_boost
+            .iter()
+            .find(|(m, _)| *m == mov)
+            .map_or(&[], |(_, boost)| boost)
+    }
+
+    /// Converts prior logits into probabilities, returning their gini impurity.
+    ///
+    /// If the policy network is unsure (its distribution has high entropy),
+    /// heuristic priors are blended in. Any `boost` priors are then blended in.
+    fn normalise_priors(
+        pos: &ChessState,
+        params: &MctsParams,
+        priors: Priors,
+        pst: f32,
+        boost: &[(Move, f32)],
+        actions: &mut [(Move, f32)],
+    ) -> f32 {
+        softmax(actions, pst);
+
+        let weight = params.heuristic_prior_weight();
+
+        if priors == Priors::Network && weight > 0.0 && actions.len() > 1 {
+            let entropy: f32 = actions
+                .iter()
+                .filter(|(_, p)| *p > 0.0)
+                .map(|(_, p)| -p * p.ln())
+                .sum();
+
+            if entropy / (actions.len() as f32).ln() > params.policy_entropy_threshold() {
+                let mut heuristic: Vec<_> = actions
+                    .iter()
+                    .map(|&(mov, _)| (mov, pos.get_heuristic_policy(mov)))
+                    .collect();
+
+                softmax(&mut heuristic, 1.0);
+
+                for ((_, p), (_, h)) in actions.iter_mut().zip(heuristic.iter()) {
+                    *p = (1.0 - weight) * *p + weight * h;
+                }
+            }
         }
 
-        let gini_impurity = (1.0 - sum_of_squares).clamp(0.0, 1.0);
-        self[node_ptr].set_gini_impurity(gini_impurity);
+        if !boost.is_empty() {
+            let weight = params.opponent_prior_weight();
+
+            for (mov, p) in actions.iter_mut() {
+                let b = boost
+                    .iter()
+                    .find(|(m, _)| m == mov)
+                    .map_or(0.0, |&(_, b)| b);
+
+                *p = (1.0 - weight) * *p + weight * b;
+            }
+        }
+
+        let sum_of_squares: f32 = actions.iter().map(|(_, p)| p * p).sum();
+        (1.0 - sum_of_squares).clamp(0.0, 1.0)
+    }
+
+    fn get_prior_feats(
+        pos: &ChessState,
+        priors: Priors,
+        policy: &PolicyNetwork,
+    ) -> Option<Accumulator<i16, { POLICY_L1 / 2 }>> {
+        (priors == Priors::Network).then(|| pos.get_policy_feats(policy))
+    }
+
+    /// Unnormalised prior logit of a move
+    fn get_prior(
+        pos: &ChessState,
+        mov: Move,
+        priors: Priors,
+        feats: &Option<Accumulator<i16, { POLICY_L1 / 2 }>>,
+        policy: &PolicyNetwork,
+    ) -> f32 {
+        match (priors, feats) {
+            (Priors::Network, Some(feats)) => pos.get_policy(mov, feats, policy),
+            (Priors::Heuristic, _) => pos.get_heuristic_policy(mov),
+            _ => 0.0,
+        }
     }
 
     pub fn propogate_proven_mates(&self, ptr: NodePtr, child_state: GameState) {
         match child_state {
             // if the child node resulted in a loss, then
-            // this node has a guaranteed win
-            GameState::Lost(n) => self[ptr].set_state(GameState::Won(n + 1)),
+            // this node has a guaranteed win, keeping the shortest
+            GameState::Lost(n) if !matches!(self[ptr].state(), GameState::Won(m) if m <= n + 1) => {
+                self[ptr].set_state(GameState::Won(n + 1));
+            }
             // if the child node resulted in a win, then check if there are
             // any non-won children, and if not, guaranteed loss for this node
             GameState::Won(n) => {
                 assert_ne!(self[ptr].num_actions(), 0);
 
-                let mut proven_loss = true;
+                // moves that haven't been added yet may not lose
+                let mut proven_loss = !self[ptr].can_widen();
                 let mut max_win_len = n;
-                let first_child_ptr = *self[ptr].actions();
+                let (first_child_ptr, num_actions) = self[ptr].children();
 
-                for action in 0..self[ptr].num_actions() {
+                for action in 0..num_actions {
                     let ptr = first_child_ptr + action;
 
                     if let GameState::Won(n) = self[ptr].state() {
@@ -300,20 +541,43 @@ impl Tree {
     }
 
     pub fn set_root_position(&mut self, new_root: &ChessState) {
+        self.reroot(new_root, true);
+    }
+
+    /// Like `set_root_position`, without reporting how the tree was reused.
+    pub fn set_root_position_quiet(&mut self, new_root: &ChessState) {
+        self.reroot(new_root, false);
+    }
+
+    fn reroot(&mut self, new_root: &ChessState, verbose: bool) {
         let t = Instant::now();
+        let report = |msg: &str| {
+            if verbose {
+                log!(Debug, "tree", "{msg}");
+            }
+        };
 
         let old_root = self.root.clone();
         self.root = new_root.clone();
+        self.hash.new_generation();
+
+        // a restriction (or boost) only applies to the search it was given for
+        let restricted = !mem::take(&mut self.root_moves).is_empty();
+        self.reply_boost.clear();
+
+        if old_root.board() != new_root.board() {
+            self.corrhist.new_root();
+        }
 
         if self.is_empty() {
             return;
         }
 
-        println!("info string attempting to reuse tree");
+        report("attempting to reuse tree");
 
         let mut found = false;
 
-        println!("info string searching for subtree");
+        report("searching for subtree");
 
         let root = self.recurse_find(self.root_node(), &old_root, new_root, 2);
 
@@ -323,21 +587,148 @@ impl Tree {
             if root != self.root_node() {
                 self[self.root_node()].clear();
                 self.copy_node_across(root, self.root_node());
-                println!("info string found subtree");
+                report("found subtree");
             } else {
-                println!("info string using current tree");
+                report("using current tree");
             }
         }
 
         if !found {
-            println!("info string no subtree found");
+            report("no subtree found");
+            self.clear_halves();
+        } else if restricted && self.rebuild_root().is_none() {
+            report("unable to rebuild root");
             self.clear_halves();
         }
 
-        println!(
-            "info string tree processing took {} microseconds",
+        report(&format!(
+            "processing took {} microseconds",
             t.elapsed().as_micros()
-        );
+        ));
+    }
+
+    /// Restrict the moves searched at the root (`go searchmoves`),
+    /// an empty list meaning all legal moves.
+    ///
+    /// Should be called after `set_root_position`, which lifts the
+    /// restriction of the previous search.
+    pub fn set_root_moves(&mut self, moves: Vec<Move>) {
+        let changed = self.root_moves != moves;
+        self.root_moves = moves;
+
+        if changed
+            && !self.is_empty()
+            && self[self.root_node()].has_children()
+            && self.rebuild_root().is_none()
+        {
+            log!(Info, "tree", "unable to rebuild root, clearing tree");
+            self.clear_halves();
+        }
+    }
+
+    /// Replace the children of the root with the moves now allowed there,
+    /// keeping the subtrees of those it already had. Priors are set when
+    /// the search relabels the root.
+    fn rebuild_root(&self) -> Option<()> {
+        let root = &self[self.root_node()];
+        let actions = root.actions_mut();
+        let (first_child_ptr, num_actions) = root.children();
+
+        let restricted = self.is_restricted(&self.root);
+        let mut moves = MoveList::default();
+
+        self.root.map_legal_moves(|mov| {
+            if !restricted || self.root_moves.contains(&mov) {
+                moves.push(mov);
+            }
+        });
+
+        if moves.is_empty() {
+            return None;
+        }
+
+        let new_ptr = self.tree[self.half()].reserve_nodes(moves.len())?;
+
+        for (action, &mov) in moves.iter().enumerate() {
+            let old_ptr = (0..num_actions)
+                .map(|i| first_child_ptr + i)
+                .find(|&ptr| self[ptr].parent_move() == mov);
+
+            match old_ptr {
+                Some(ptr) => self.copy_node_across(ptr, new_ptr + action)?,
+                None => self[new_ptr + action].set_new(mov, 0.0),
+            }
+        }
+
+        actions.set(new_ptr);
+        root.set_num_actions(moves.len());
+        root.set_num_moves(moves.len());
+
+        Some(())
+    }
+
+    /// Whether `searchmoves` restricts the moves of `pos` (the root), which
+    /// it doesn't if none of them are legal there.
+    fn is_restricted(&self, pos: &ChessState) -> bool {
+        let mut any_legal = false;
+
+        if !self.root_moves.is_empty() {
+            pos.map_legal_moves(|mov| any_legal |= self.root_moves.contains(&mov));
+        }
+
+        any_legal
+    }
+
+    /// Blend `boost`, priors for the replies to each root move (e.g. the
+    /// opponent's past move frequencies), into the priors of the root's
+    /// children when they are expanded or relabelled.
+    ///
+    /// Should be called after `set_root_position`, which clears it.
+    pub fn set_reply_boost(&mut self, boost: Vec<(Move, Vec<(Move, f32)>)>) {
+        self.reply_boost = boost;
+    }
+
+    /// Number of nodes in the subtree rooted at `ptr`, including those
+    /// whose children haven't been copied to the current half yet.
+    pub fn subtree_size(&self, ptr: NodePtr) -> usize {
+        let mut stack = vec![ptr];
+        let mut size = 0;
+
+        while let Some(ptr) = stack.pop() {
+            size += 1;
+
+            let (first_child_ptr, num_actions) = self[ptr].children();
+
+            if first_child_ptr.is_null() {
+                continue;
+            }
+
+            for action in 0..num_actions {
+                stack.push(first_child_ptr + action);
+            }
+        }
+
+        size
+    }
+
+    /// Decay the visits of every node in the subtree rooted at `ptr`.
+    pub fn decay_visits(&self, ptr: NodePtr, factor: f32) {
+        let mut stack = vec![ptr];
+
+        while let Some(ptr) = stack.pop() {
+            let node = &self[ptr];
+            node.decay_visits(factor);
+
+            let (first_child_ptr, num_actions) = node.children();
+
+            if first_child_ptr.is_null() {
+                continue;
+            }
+
+            for action in 0..num_actions {
+                stack.push(first_child_ptr + action);
+            }
+        }
     }
 
     fn recurse_find(
@@ -355,13 +746,13 @@ impl Tree {
             return NodePtr::NULL;
         }
 
-        let first_child_ptr = { *self[start].actions() };
+        let (first_child_ptr, num_actions) = self[start].children();
 
         if first_child_ptr.is_null() {
             return NodePtr::NULL;
         }
 
-        for action in 0..self[start].num_actions() {
+        for action in 0..num_actions {
             let mut child_board = this_board.clone();
 
             let child_ptr = first_child_ptr + action;
@@ -383,9 +774,9 @@ impl Tree {
         let mut best_child = usize::MAX;
         let mut best_score = f32::NEG_INFINITY;
 
-        let first_child_ptr = { *self[ptr].actions() };
+        let (first_child_ptr, num_actions) = self[ptr].children();
 
-        for action in 0..self[ptr].num_actions() {
+        for action in 0..num_actions {
             let score = key(&self[first_child_ptr + action]);
 
             if score > best_score {
@@ -397,18 +788,50 @@ impl Tree {
         best_child
     }
 
+    /// Total prior of the children that have been visited.
+    pub fn visited_policy(&self, ptr: NodePtr) -> f32 {
+        let (first_child_ptr, num_actions) = self[ptr].children();
+
+        (0..num_actions)
+            .map(|action| &self[first_child_ptr + action])
+            .filter(|child| child.visits() > 0)
+            .map(Node::policy)
+            .sum()
+    }
+
     pub fn get_best_child(&self, ptr: NodePtr) -> usize {
-        self.get_best_child_by_key(ptr, |child| {
-            if child.visits() == 0 {
-                f32::NEG_INFINITY
-            } else {
-                match child.state() {
-                    GameState::Lost(n) => 1.0 + f32::from(n),
-                    GameState::Won(n) => f32::from(n) - 256.0,
-                    GameState::Draw => 0.5,
-                    GameState::Ongoing => child.q(),
-                }
+        self.get_best_child_by_key(ptr, Self::best_child_key)
+    }
+
+    /// How good a child is as the move to play, proven results first.
+    pub fn best_child_key(child: &Node) -> f32 {
+        if child.visits() == 0 {
+            f32::NEG_INFINITY
+        } else {
+            match child.state() {
+                GameState::Lost(n) => 1.0 + f32::from(n),
+                GameState::Won(n) => f32::from(n) - 256.0,
+                GameState::Draw => 0.5,
+                GameState::Ongoing => child.q(),
             }
-        })
+        }
+    }
+}
+
+fn softmax(actions: &mut [(Move, f32)], temperature: f32) {
+    let max = actions
+        .iter()
+        .map(|&(_, p)| p)
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    let mut total = 0.0;
+
+    for (_, policy) in actions.iter_mut() {
+        *policy = ((*policy - max) / temperature).exp();
+        total += *policy;
+    }
+
+    for (_, policy) in actions.iter_mut() {
+        *policy /= total;
     }
 }
@@ -1,10 +1,18 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    OnceLock,
+};
 
-use super::{Node, NodePtr};
-use crate::chess::GameState;
+use super::{pages::PageSlice, Node, NodePtr};
+use crate::{chess::GameState, pool};
+
+/// Nodes are allocated in chunks of this many as the half fills up, rather
+/// than all at once, so a large `Hash` only costs memory once it is used.
+const CHUNK_SIZE: usize = 1 << 16;
 
 pub struct TreeHalf {
-    pub(super) nodes: Vec<Node>,
+    chunks: Vec<OnceLock<PageSlice<Node>>>,
+    size: usize,
     used: AtomicUsize,
     half: bool,
 }
@@ -13,49 +21,43 @@ impl std::ops::Index<NodePtr> for TreeHalf {
     type Output = Node;
 
     fn index(&self, index: NodePtr) -> &Self::Output {
-        &self.nodes[index.idx()]
+        let idx = index.idx();
+        let chunk = self.chunks[idx / CHUNK_SIZE]
+            .get()
+            .expect("node has not been reserved");
+
+        &chunk[idx % CHUNK_SIZE]
     }
 }
 
 impl TreeHalf {
-    pub fn new(size: usize, half: bool, threads: usize) -> Self {
-        let mut res = Self {
-            nodes: Vec::new(),
+    pub fn new(size: usize, half: bool) -> Self {
+        Self {
+            chunks: (0..size.div_ceil(CHUNK_SIZE))
+                .map(|_| OnceLock::new())
+                .collect(),
+            size,
             used: AtomicUsize::new(0),
             half,
-        };
-
-        res.nodes.reserve_exact(size);
-
-        unsafe {
-            use std::mem::MaybeUninit;
-            let chunk_size = size.div_ceil(threads);
-            let ptr = res.nodes.as_mut_ptr().cast();
-            let uninit: &mut [MaybeUninit<Node>] = std::slice::from_raw_parts_mut(ptr, size);
-
-            std::thread::scope(|s| {
-                for chunk in uninit.chunks_mut(chunk_size) {
-                    s.spawn(|| {
-                        for node in chunk {
-                            node.write(Node::new(GameState::Ongoing));
-                        }
-                    });
-                }
-            });
-
-            res.nodes.set_len(size);
         }
-
-        res
     }
 
     pub fn reserve_nodes(&self, num: usize) -> Option<NodePtr> {
         let idx = self.used.fetch_add(num, Ordering::Relaxed);
 
-        if idx + num > self.nodes.len() {
+        if idx + num > self.size {
             return None;
         }
 
+        if num > 0 {
+            for chunk in idx / CHUNK_SIZE..=(idx + num - 1) / CHUNK_SIZE {
+                self.chunks[chunk].get_or_init(|| {
+                    let len = CHUNK_SIZE.min(self.size - chunk * CHUNK_SIZE);
+                    PageSlice::from_fn(len, || Node::new(GameState::Ongoing))
+                });
+            }
+        }
+
         Some(NodePtr::new(self.half, idx as u32))
     }
 
@@ -64,32 +66,18 @@ impl TreeHalf {
     }
 
     pub fn clear_ptrs(&self, threads: usize) {
-        if threads == 1 {
-            Self::clear_ptrs_single_threaded(self.half, &self.nodes);
-        } else {
-            self.clear_ptrs_multi_threaded(threads);
-        }
-    }
-
-    fn clear_ptrs_single_threaded(half: bool, nodes: &[Node]) {
-        for node in nodes {
-            let actions_half = { node.actions().half() };
-
-            if actions_half != half {
-                node.clear_actions();
-            }
-        }
-    }
-
-    fn clear_ptrs_multi_threaded(&self, threads: usize) {
-        std::thread::scope(|s| {
-            let chunk_size = self.nodes.len().div_ceil(threads);
-
-            s.spawn(move || {
-                for node_chunk in self.nodes.chunks(chunk_size) {
-                    Self::clear_ptrs_single_threaded(self.half, node_chunk)
+        pool::for_each_chunk(&self.chunks, threads, |chunks| {
+            for node in chunks
+                .iter()
+                .filter_map(OnceLock::get)
+                .flat_map(|nodes| nodes.iter())
+            {
+                let actions_half = node.actions().half();
+
+                if actions_half != self.half {
+                    node.clear_actions();
                 }
-            });
+            }
         });
     }
 
@@ -101,7 +89,11 @@ impl TreeHalf {
         self.used.load(Ordering::Relaxed)
     }
 
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
     pub fn is_full(&self) -> bool {
-        self.used() >= self.nodes.len()
+        self.used() >= self.size
     }
 }
@@ -1,23 +1,34 @@
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::pages;
+use crate::pool;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct HashEntry {
-    hash: u16,
+    hash: u32,
     q: u16,
+    /// Search that the entry was last written in.
+    generation: u8,
+    /// Visits of the node the entry was written from, saturating.
+    visits: u8,
 }
 
 impl HashEntry {
     pub fn q(&self) -> f32 {
         f32::from(self.q) / f32::from(u16::MAX)
     }
+
+    pub fn visits(&self) -> i32 {
+        i32::from(self.visits)
+    }
 }
 
 #[derive(Default)]
-struct HashEntryInternal(AtomicU32);
+struct HashEntryInternal(AtomicU64);
 
 impl Clone for HashEntryInternal {
     fn clone(&self) -> Self {
-        Self(AtomicU32::new(self.0.load(Ordering::Relaxed)))
+        Self(AtomicU64::new(self.0.load(Ordering::Relaxed)))
     }
 }
 
@@ -27,22 +38,31 @@ impl From<&HashEntryInternal> for HashEntry {
     }
 }
 
-impl From<HashEntry> for u32 {
+impl From<HashEntry> for u64 {
     fn from(value: HashEntry) -> Self {
         unsafe { std::mem::transmute(value) }
     }
 }
 
+/// Entries are grouped in buckets, so that entries written in the
+/// current search can be kept in favour of those from older ones.
 pub struct HashTable {
     table: Vec<HashEntryInternal>,
+    generation: u8,
 }
 
 impl HashTable {
+    const BUCKET_SIZE: usize = 2;
+
     pub fn new(size: usize, threads: usize) -> Self {
-        let chunk_size = size.div_ceil(threads);
+        let size = size.max(Self::BUCKET_SIZE);
 
-        let mut table = HashTable { table: Vec::new() };
+        let mut table = HashTable {
+            table: Vec::new(),
+            generation: 0,
+        };
         table.table.reserve_exact(size);
+        pages::advise(&table.table);
 
         unsafe {
             use std::mem::{size_of, MaybeUninit};
@@ -50,12 +70,8 @@ impl HashTable {
             let uninit: &mut [MaybeUninit<u8>] =
                 std::slice::from_raw_parts_mut(ptr, size * size_of::<HashEntryInternal>());
 
-            std::thread::scope(|s| {
-                for chunk in uninit.chunks_mut(chunk_size) {
-                    s.spawn(|| {
-                        chunk.as_mut_ptr().write_bytes(0, chunk.len());
-                    });
-                }
+            pool::for_each_chunk_mut(uninit, threads, |chunk| {
+                chunk.as_mut_ptr().write_bytes(0, chunk.len());
             });
 
             table.table.set_len(size);
@@ -65,48 +81,96 @@ impl HashTable {
     }
 
     pub fn clear(&mut self, threads: usize) {
-        let chunk_size = self.table.len().div_ceil(threads);
-
-        std::thread::scope(|s| {
-            for chunk in self.table.chunks_mut(chunk_size) {
-                s.spawn(|| {
-                    for entry in chunk.iter_mut() {
-                        *entry = HashEntryInternal::default();
-                    }
-                });
+        self.generation = 0;
+
+        pool::for_each_chunk_mut(&mut self.table, threads, |chunk| {
+            for entry in chunk.iter_mut() {
+                *entry = HashEntryInternal::default();
             }
         });
     }
 
-    pub fn fetch(&self, hash: u64) -> HashEntry {
-        let idx = hash % (self.table.len() as u64);
-        HashEntry::from(&self.table[idx as usize])
+    /// Start a new search, ageing all existing entries.
+    pub fn new_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
     }
 
-    fn key(hash: u64) -> u16 {
-        (hash >> 48) as u16
+    fn bucket(&self, hash: u64) -> &[HashEntryInternal] {
+        let buckets = self.table.len() / Self::BUCKET_SIZE;
+        let idx = (hash % buckets as u64) as usize * Self::BUCKET_SIZE;
+        &self.table[idx..idx + Self::BUCKET_SIZE]
     }
 
-    pub fn get(&self, hash: u64) -> Option<HashEntry> {
-        let entry = self.fetch(hash);
+    fn key(hash: u64) -> u32 {
+        (hash >> 32) as u32
+    }
 
-        if entry.hash == Self::key(hash) {
-            Some(entry)
-        } else {
-            None
-        }
+    pub fn prefetch(&self, hash: u64) {
+        crate::prefetch(&self.bucket(hash)[0]);
     }
 
-    pub fn push(&self, hash: u64, q: f32) {
-        let idx = hash % (self.table.len() as u64);
+    pub fn get(&self, hash: u64) -> Option<HashEntry> {
+        self.bucket(hash)
+            .iter()
+            .map(HashEntry::from)
+            .find(|entry| entry.hash == Self::key(hash))
+    }
+
+    pub fn push(&self, hash: u64, q: f32, visits: i32) {
+        let key = Self::key(hash);
+        let bucket = self.bucket(hash);
+
+        // overwrite the entry for this position if there is one,
+        // otherwise the one from the oldest search
+        let slot = bucket
+            .iter()
+            .position(|entry| HashEntry::from(entry).hash == key)
+            .unwrap_or_else(|| {
+                (0..bucket.len())
+                    .max_by_key(|&i| self.age(HashEntry::from(&bucket[i])))
+                    .unwrap()
+            });
 
         let entry = HashEntry {
-            hash: Self::key(hash),
+            hash: key,
             q: (q * f32::from(u16::MAX)) as u16,
+            generation: self.generation,
+            visits: visits.clamp(0, i32::from(u8::MAX)) as u8,
         };
 
-        self.table[idx as usize]
-            .0
-            .store(u32::from(entry), Ordering::Relaxed)
+        bucket[slot].0.store(u64::from(entry), Ordering::Relaxed)
+    }
+
+    /// Whether the entry was written by an earlier search.
+    pub fn is_older(&self, entry: HashEntry) -> bool {
+        !matches!(self.age(entry), 0 | u8::MAX)
+    }
+
+    /// Number of searches since the entry was written, or `u8::MAX` if
+    /// it is empty.
+    fn age(&self, entry: HashEntry) -> u8 {
+        if u64::from(entry) == 0 {
+            u8::MAX
+        } else {
+            self.generation.wrapping_sub(entry.generation)
+        }
+    }
+
+    /// Permille of (a sample of) entries written in the current search,
+    /// and in older searches.
+    pub fn usage(&self) -> (usize, usize) {
+        let sample = self.table.len().min(1000);
+        let mut current = 0;
+        let mut older = 0;
+
+        for entry in &self.table[..sample] {
+            match self.age(HashEntry::from(entry)) {
+                0 => current += 1,
+                u8::MAX => {}
+                _ => older += 1,
+            }
+        }
+
+        (current * 1000 / sample, older * 1000 / sample)
     }
 }
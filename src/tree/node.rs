@@ -1,9 +1,6 @@
 use std::{
     ops::Add,
-    sync::{
-        atomic::{AtomicI32, AtomicU16, AtomicU32, AtomicU8, Ordering},
-        RwLock, RwLockReadGuard, RwLockWriteGuard,
-    },
+    sync::atomic::{AtomicBool, AtomicI32, AtomicU16, AtomicU32, AtomicU8, Ordering},
 };
 
 use crate::chess::{GameState, Move};
@@ -47,10 +44,52 @@ impl Add<usize> for NodePtr {
     }
 }
 
+/// The search statistics of a node, without its children.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct NodeStats {
+    pub mov: u16,
+    pub policy: u16,
+    pub state: u16,
+    pub visits: i32,
+    pub q: u32,
+    pub sq_q: u32,
+    pub gini_impurity: u32,
+}
+
+/// Exclusive access to the children of a node, from `Node::actions_mut`.
+pub struct ActionsGuard<'a> {
+    node: &'a Node,
+}
+
+impl ActionsGuard<'_> {
+    pub fn get(&self) -> NodePtr {
+        self.node.actions()
+    }
+
+    /// Publish new children, which must already be written.
+    pub fn set(&self, ptr: NodePtr) {
+        self.node.actions.store(ptr.inner(), Ordering::Release);
+    }
+}
+
+impl Drop for ActionsGuard<'_> {
+    fn drop(&mut self) {
+        self.node.actions_locked.store(false, Ordering::Release);
+    }
+}
+
 #[derive(Debug)]
 pub struct Node {
-    actions: RwLock<NodePtr>,
+    /// First child, read without locking: children are written before
+    /// it is published, and stay valid until the tree half they are in
+    /// is cleared.
+    actions: AtomicU32,
+    /// Held while the children are changed, see `actions_mut`.
+    actions_locked: AtomicBool,
     num_actions: AtomicU8,
+    /// Legal moves of the node, of which just the first `num_actions` (by
+    /// policy) are children until it is widened, or 0 if not known.
+    num_moves: AtomicU8,
     state: AtomicU16,
     threads: AtomicU16,
     mov: AtomicU16,
@@ -62,10 +101,19 @@ pub struct Node {
 }
 
 impl Node {
+    /// Most children a node can have, as `num_actions` is a `u8`.
+    pub const MAX_ACTIONS: usize = u8::MAX as usize;
+
+    /// Visits are saturated well below `i32::MAX`, so that concurrent
+    /// increments can't overflow before the count is clamped again.
+    pub const MAX_VISITS: i32 = i32::MAX / 2;
+
     pub fn new(state: GameState) -> Self {
         Node {
-            actions: RwLock::new(NodePtr::NULL),
+            actions: AtomicU32::new(NodePtr::NULL.inner()),
+            actions_locked: AtomicBool::new(false),
             num_actions: AtomicU8::new(0),
+            num_moves: AtomicU8::new(0),
             state: AtomicU16::new(u16::from(state)),
             threads: AtomicU16::new(0),
             mov: AtomicU16::new(0),
@@ -88,11 +136,36 @@ impl Node {
     }
 
     pub fn num_actions(&self) -> usize {
-        usize::from(self.num_actions.load(Ordering::Relaxed))
+        usize::from(self.num_actions.load(Ordering::Acquire))
     }
 
+    /// Must be called after the children are published with
+    /// `ActionsGuard::set`, see `children`.
     pub fn set_num_actions(&self, num: usize) {
-        self.num_actions.store(num as u8, Ordering::Relaxed);
+        let num = u8::try_from(num).expect("too many actions for a node");
+        self.num_actions.store(num, Ordering::Release);
+    }
+
+    /// The first child and the number of children. The count is loaded
+    /// first, so that every child it covers is in the block pointed to,
+    /// even if the node is being widened.
+    pub fn children(&self) -> (NodePtr, usize) {
+        let num_actions = self.num_actions();
+        (self.actions(), num_actions)
+    }
+
+    pub fn num_moves(&self) -> usize {
+        usize::from(self.num_moves.load(Ordering::Relaxed))
+    }
+
+    pub fn set_num_moves(&self, num: usize) {
+        let num = u8::try_from(num).expect("too many moves for a node");
+        self.num_moves.store(num, Ordering::Relaxed);
+    }
+
+    /// Whether some legal moves have not been added as children yet.
+    pub fn can_widen(&self) -> bool {
+        self.num_moves.load(Ordering::Relaxed) > self.num_actions.load(Ordering::Relaxed)
     }
 
     pub fn threads(&self) -> u16 {
@@ -127,12 +200,28 @@ impl Node {
         self.threads.fetch_sub(1, Ordering::Relaxed);
     }
 
-    pub fn actions(&self) -> RwLockReadGuard<NodePtr> {
-        self.actions.read().unwrap()
+    pub fn clear_threads(&self) {
+        self.threads.store(0, Ordering::Relaxed);
+    }
+
+    pub fn actions(&self) -> NodePtr {
+        NodePtr::from_raw(self.actions.load(Ordering::Acquire))
     }
 
-    pub fn actions_mut(&self) -> RwLockWriteGuard<NodePtr> {
-        self.actions.write().unwrap()
+    /// Lock the children for expanding, widening or moving them, which
+    /// only one thread may do at a time. Readers aren't blocked.
+    pub fn actions_mut(&self) -> ActionsGuard<'_> {
+        while self
+            .actions_locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.actions_locked.load(Ordering::Relaxed) {
+                std::thread::yield_now();
+            }
+        }
+
+        ActionsGuard { node: self }
     }
 
     pub fn state(&self) -> GameState {
@@ -170,8 +259,9 @@ impl Node {
     }
 
     pub fn clear_actions(&self) {
-        *self.actions.write().unwrap() = NodePtr::NULL;
         self.num_actions.store(0, Ordering::Relaxed);
+        self.actions.store(NodePtr::NULL.inner(), Ordering::Relaxed);
+        self.num_moves.store(0, Ordering::Relaxed);
     }
 
     pub fn parent_move(&self) -> Move {
@@ -182,6 +272,7 @@ impl Node {
         use std::sync::atomic::Ordering::Relaxed;
 
         self.threads.store(other.threads.load(Relaxed), Relaxed);
+        self.num_moves.store(other.num_moves.load(Relaxed), Relaxed);
         self.mov.store(other.mov.load(Relaxed), Relaxed);
         self.policy.store(other.policy.load(Relaxed), Relaxed);
         self.state.store(other.state.load(Relaxed), Relaxed);
@@ -192,6 +283,33 @@ impl Node {
         self.sq_q.store(other.sq_q.load(Relaxed), Relaxed);
     }
 
+    pub(super) fn stats(&self) -> NodeStats {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        NodeStats {
+            mov: self.mov.load(Relaxed),
+            policy: self.policy.load(Relaxed),
+            state: self.state.load(Relaxed),
+            visits: self.visits.load(Relaxed),
+            q: self.q.load(Relaxed),
+            sq_q: self.sq_q.load(Relaxed),
+            gini_impurity: self.gini_impurity.load(Relaxed),
+        }
+    }
+
+    pub(super) fn set_stats(&self, stats: NodeStats) {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        self.clear();
+        self.mov.store(stats.mov, Relaxed);
+        self.policy.store(stats.policy, Relaxed);
+        self.state.store(stats.state, Relaxed);
+        self.visits.store(stats.visits, Relaxed);
+        self.q.store(stats.q, Relaxed);
+        self.sq_q.store(stats.sq_q, Relaxed);
+        self.gini_impurity.store(stats.gini_impurity, Relaxed);
+    }
+
     pub fn clear(&self) {
         self.clear_actions();
         self.set_state(GameState::Ongoing);
@@ -202,9 +320,35 @@ impl Node {
         self.threads.store(0, Ordering::Relaxed);
     }
 
+    /// Scale down the visit count (keeping at least one visit), so
+    /// that `q` becomes less resistant to new results.
+    pub fn decay_visits(&self, factor: f32) {
+        let visits = self.visits();
+
+        if visits > 1 {
+            let decayed = (visits as f32 * factor) as i32;
+            self.visits.store(decayed.max(1), Ordering::Relaxed);
+        }
+    }
+
+    /// Give a node with a single visit the weight of `visits`, e.g. when
+    /// its Q came from a transposition searched that many times.
+    pub fn seed_visits(&self, visits: i32) {
+        let _ =
+            self.visits
+                .compare_exchange(1, visits.max(1), Ordering::Relaxed, Ordering::Relaxed);
+    }
+
     pub fn update(&self, result: f32) -> f32 {
         let r = f64::from(result);
-        let v = f64::from(self.visits.fetch_add(1, Ordering::Relaxed));
+        let visits = self.visits.fetch_add(1, Ordering::Relaxed);
+
+        // past this, results are averaged in as if the count were constant
+        if visits >= Self::MAX_VISITS {
+            self.visits.store(Self::MAX_VISITS, Ordering::Relaxed);
+        }
+
+        let v = f64::from(visits.min(Self::MAX_VISITS));
 
         let q = (self.q64() * v + r) / (v + 1.0);
         let sq_q = (self.sq_q() * v + r.powi(2)) / (v + 1.0);
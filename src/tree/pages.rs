@@ -0,0 +1,141 @@
+//! Backing of the tree halves and hash table with 2MB huge pages
+//! (`LargePages`), which cuts TLB misses in trees of many gigabytes.
+//!
+//! On Linux this asks for transparent huge pages with `madvise`, before
+//! the memory is first written. Windows large pages need a privilege
+//! that engines are rarely run with, so elsewhere this has no effect.
+//!
+//! Off by default, as a huge page is backed in full once any of it is
+//! written, so a partly used tree can take more memory than without.
+
+use std::{
+    alloc::{self, Layout},
+    ops::Deref,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+const HUGE_PAGE: usize = 2 * 1024 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Applies to tables allocated afterwards.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    cfg!(target_os = "linux") && ENABLED.load(Ordering::Relaxed)
+}
+
+/// Advise that the (untouched) capacity of `vec` be backed by huge pages.
+///
+/// Only the huge pages that fit entirely in the capacity can be, so this
+/// is for allocations of many huge pages, otherwise see `PageSlice`.
+pub fn advise<T>(vec: &Vec<T>) {
+    if enabled() {
+        let bytes = vec.capacity() * std::mem::size_of::<T>();
+        advise_range(vec.as_ptr() as usize, bytes);
+    }
+}
+
+/// A boxed slice that is aligned to a huge page when they are enabled,
+/// so that all of it can be backed by them however short it is.
+pub struct PageSlice<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    layout: Layout,
+}
+
+// owns its elements, like a `Box<[T]>`
+unsafe impl<T: Send> Send for PageSlice<T> {}
+unsafe impl<T: Sync> Sync for PageSlice<T> {}
+
+impl<T> PageSlice<T> {
+    pub fn from_fn(len: usize, mut f: impl FnMut() -> T) -> Self {
+        let huge = enabled();
+        let align = if huge {
+            HUGE_PAGE
+        } else {
+            std::mem::align_of::<T>()
+        };
+
+        // a short slice still gets a whole huge page
+        let layout = Layout::array::<T>(len)
+            .and_then(|layout| layout.align_to(align))
+            .expect("allocation too large")
+            .pad_to_align();
+
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            let raw = unsafe { alloc::alloc(layout) };
+
+            let Some(ptr) = NonNull::new(raw.cast::<T>()) else {
+                alloc::handle_alloc_error(layout)
+            };
+
+            if huge {
+                advise_range(raw as usize, layout.size());
+            }
+
+            for i in 0..len {
+                unsafe { ptr.as_ptr().add(i).write(f()) };
+            }
+
+            ptr
+        };
+
+        Self { ptr, len, layout }
+    }
+}
+
+impl<T> Deref for PageSlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for PageSlice<T> {
+    fn drop(&mut self) {
+        unsafe {
+            std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+                self.ptr.as_ptr(),
+                self.len,
+            ));
+
+            if self.layout.size() != 0 {
+                alloc::dealloc(self.ptr.as_ptr().cast(), self.layout);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn advise_range(start: usize, bytes: usize) {
+    const MADV_HUGEPAGE: i32 = 14;
+
+    extern "C" {
+        fn madvise(addr: *mut std::ffi::c_void, len: usize, advice: i32) -> i32;
+    }
+
+    // huge pages can only back the aligned part of the range
+    let aligned_start = start.next_multiple_of(HUGE_PAGE);
+    let aligned_end = (start + bytes) / HUGE_PAGE * HUGE_PAGE;
+
+    if aligned_end > aligned_start {
+        // failure (e.g. THP disabled) just leaves normal pages
+        unsafe {
+            madvise(
+                aligned_start as *mut std::ffi::c_void,
+                aligned_end - aligned_start,
+                MADV_HUGEPAGE,
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_range(_start: usize, _bytes: usize) {}
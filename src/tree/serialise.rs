@@ -0,0 +1,198 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use super::{node::NodeStats, NodePtr, Tree};
+use crate::chess::{ChessState, Move};
+
+const MAGIC: &[u8; 8] = b"MONTYTRE";
+const VERSION: u32 = 2;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+impl Tree {
+    /// Write the tree below the root to `path`.
+    ///
+    /// Nodes are written in breadth-first order, so that the children of
+    /// each node are contiguous and follow all nodes before it, and child
+    /// pointers don't need to be stored.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        let fen = self.root.as_fen();
+        writer.write_all(&(fen.len() as u32).to_le_bytes())?;
+        writer.write_all(fen.as_bytes())?;
+
+        let mut order = Vec::new();
+
+        if !self.is_empty() {
+            order.push(self.root_node());
+        }
+
+        let mut i = 0;
+        while i < order.len() {
+            let node = &self[order[i]];
+            let (first_child_ptr, num_actions) = node.children();
+
+            for action in 0..num_actions {
+                order.push(first_child_ptr + action);
+            }
+
+            i += 1;
+        }
+
+        writer.write_all(&(order.len() as u32).to_le_bytes())?;
+
+        for ptr in order {
+            let node = &self[ptr];
+            let stats = node.stats();
+
+            writer.write_all(&stats.mov.to_le_bytes())?;
+            writer.write_all(&stats.policy.to_le_bytes())?;
+            writer.write_all(&stats.state.to_le_bytes())?;
+            writer.write_all(&[node.num_actions() as u8])?;
+            writer.write_all(&[node.num_moves() as u8])?;
+            writer.write_all(&stats.visits.to_le_bytes())?;
+            writer.write_all(&stats.q.to_le_bytes())?;
+            writer.write_all(&stats.sq_q.to_le_bytes())?;
+            writer.write_all(&stats.gini_impurity.to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    /// Replace the tree (and root position) with one written by `save`.
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        if &read_array::<8>(&mut reader)? != MAGIC {
+            return Err(invalid("not a tree file"));
+        }
+
+        if u32::from_le_bytes(read_array(&mut reader)?) != VERSION {
+            return Err(invalid("unsupported tree file version"));
+        }
+
+        let fen_len = u32::from_le_bytes(read_array(&mut reader)?) as usize;
+        let mut fen = vec![0; fen_len];
+        reader.read_exact(&mut fen)?;
+        let fen = String::from_utf8(fen).map_err(|_| invalid("invalid root fen"))?;
+        let root = ChessState::try_from_fen(&fen).map_err(|_| invalid("invalid root fen"))?;
+
+        let count = u32::from_le_bytes(read_array(&mut reader)?) as usize;
+
+        if count > self.tree[self.half()].capacity() {
+            return Err(invalid("tree does not fit in the current hash size"));
+        }
+
+        self.clear_halves();
+        self.root = root;
+
+        let result = self.load_nodes(&mut reader, count);
+
+        if result.is_err() {
+            self.clear_halves();
+        }
+
+        result
+    }
+
+    fn load_nodes(&self, reader: &mut impl Read, count: usize) -> io::Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+
+        let base = self.tree[self.half()].reserve_nodes(count).unwrap();
+        let ptr = |idx: usize| NodePtr::new(base.half(), (base.idx() + idx) as u32);
+
+        // index of the first node whose parent hasn't been read yet
+        let mut next_child = 1;
+
+        // positions of the nodes whose children are still to be read, with
+        // their legal moves and how many children are left, in file order
+        let mut parents: VecDeque<(ChessState, Vec<Move>, usize)> = VecDeque::new();
+
+        for i in 0..count {
+            let mov = u16::from_le_bytes(read_array(reader)?);
+            let policy = u16::from_le_bytes(read_array(reader)?);
+            let state = u16::from_le_bytes(read_array(reader)?);
+            let num_actions = usize::from(read_array::<1>(reader)?[0]);
+            let num_moves = usize::from(read_array::<1>(reader)?[0]);
+
+            let stats = NodeStats {
+                mov,
+                policy,
+                state,
+                visits: i32::from_le_bytes(read_array(reader)?),
+                q: u32::from_le_bytes(read_array(reader)?),
+                sq_q: u32::from_le_bytes(read_array(reader)?),
+                gini_impurity: u32::from_le_bytes(read_array(reader)?),
+            };
+
+            if state >> 8 > 3 {
+                return Err(invalid("invalid node state"));
+            }
+
+            if num_actions > num_moves {
+                return Err(invalid("more children than moves"));
+            }
+
+            let pos = if i == 0 {
+                self.root.clone()
+            } else {
+                let Some((parent, legal, left)) = parents.front_mut() else {
+                    return Err(invalid("node without a parent"));
+                };
+
+                let mov = Move::from(mov);
+
+                if !legal.contains(&mov) {
+                    return Err(invalid("illegal move"));
+                }
+
+                let mut pos = parent.clone();
+                pos.make_move(mov);
+
+                *left -= 1;
+                if *left == 0 {
+                    parents.pop_front();
+                }
+
+                pos
+            };
+
+            let node = &self[ptr(i)];
+            node.set_stats(stats);
+
+            if num_actions > 0 {
+                if next_child + num_actions > count {
+                    return Err(invalid("truncated tree"));
+                }
+
+                node.actions_mut().set(ptr(next_child));
+                node.set_num_actions(num_actions);
+                node.set_num_moves(num_moves);
+                next_child += num_actions;
+
+                let mut legal = Vec::new();
+                pos.map_legal_moves(|mov| legal.push(mov));
+                parents.push_back((pos, legal, num_actions));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,24 +1,83 @@
 use crate::{
-    chess::{ChessState, Move},
-    mcts::{Limits, MctsParams, SearchHelpers, Searcher},
+    chess::{parse_pgn, ChessState, EpdRecord, Move},
+    log,
+    mcts::{
+        Limits, MctsParams, OutputFormat, Priors, RootSelection, SearchHelpers, SearchOptions,
+        Searcher,
+    },
     networks::{PolicyNetwork, ValueNetwork},
-    tree::Tree,
+    openings,
+    opponent::{self, Opponent, OpponentHook, OpponentProfile, ENGINE_ELO},
+    pool, profile,
+    rng::Rng,
+    score::{self, Score},
+    strength::Strength,
+    tree::{self, Tree},
+    xboard,
 };
 
 use std::{
-    io, process,
+    fs, io, process,
     sync::atomic::{AtomicBool, Ordering},
     time::Instant,
 };
 
 pub fn run(policy: &PolicyNetwork, value: &ValueNetwork) {
+    run_with_params(policy, value, MctsParams::default());
+}
+
+/// Like `run`, starting from `params` rather than the defaults.
+pub fn run_with_params(policy: &PolicyNetwork, value: &ValueNetwork, params: MctsParams) {
+    run_loop(policy, value, params, opponent::rating_contempt);
+}
+
+/// Like `run`, with `hook` customising the search for each opponent
+/// given through `UCI_Opponent` when `AutoContempt` is enabled.
+pub fn run_with_opponent_hook(policy: &PolicyNetwork, value: &ValueNetwork, hook: OpponentHook) {
+    run_loop(policy, value, MctsParams::default(), hook);
+}
+
+/// Params given on the command line as `--params <file.toml>` (in place of
+/// the first argument, which is replaced by the one after the file), or
+/// the defaults.
+pub fn params_from_args(
+    arg1: &mut Option<String>,
+    args: &mut impl Iterator<Item = String>,
+) -> MctsParams {
+    if arg1.as_deref() != Some("--params") {
+        return MctsParams::default();
+    }
+
+    let path = args.next().unwrap_or_default();
+    *arg1 = args.next();
+
+    MctsParams::load(&path).unwrap_or_else(|err| {
+        println!("info string failed to load params from {path}: {err}");
+        MctsParams::default()
+    })
+}
+
+fn run_loop(policy: &PolicyNetwork, value: &ValueNetwork, params: MctsParams, hook: OpponentHook) {
     let mut pos = ChessState::default();
     let mut root_game_ply = 0;
-    let mut params = MctsParams::default();
+    let mut params = params;
+    let mut options = SearchOptions::default();
     let mut tree = Tree::new_mb(64, 1);
     let mut report_moves = false;
     let mut threads = 1;
     let mut move_overhead = 40;
+    let mut own_book_plies = 0;
+    let mut strength = Strength::default();
+    let mut time_audit = Vec::new();
+    let mut opponent = OpponentInfo {
+        opponent: None,
+        profile_dir: None,
+        profile: OpponentProfile::default(),
+        auto_contempt: false,
+        hook,
+    };
+    let mut game_moves = Vec::new();
+    let mut our_side = None;
 
     let mut stored_message: Option<String> = None;
 
@@ -43,32 +102,56 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork) {
         let cmd = *commands.first().unwrap_or(&"oops");
         match cmd {
             "isready" => println!("readyok"),
+            // sent first by XBoard/WinBoard
+            "xboard" => {
+                xboard::run(policy, value);
+                return;
+            }
             "setoption" => setoption(
                 &commands,
                 &mut params,
+                &mut options,
                 &mut report_moves,
                 &mut tree,
                 &mut threads,
                 &mut move_overhead,
+                &mut own_book_plies,
+                &mut opponent,
+                &mut strength,
             ),
-            "position" => position(commands, &mut pos),
+            "position" => position(commands, &mut pos, &mut game_moves),
             "go" => {
                 // increment game ply every time `go` is called
                 root_game_ply += 2;
+                our_side = Some(pos.stm());
+
+                if let Some(mov) = book_move(&pos, &game_moves, own_book_plies) {
+                    println!("info string book move");
+                    println!("bestmove {}", pos.conv_mov_to_str(mov));
+                    continue;
+                }
+
+                let temperature = options.temperature_at(game_moves.len() as u32);
 
-                go(
+                let move_time = go(
                     &commands,
                     &mut tree,
                     &pos,
                     root_game_ply,
                     &params,
+                    &options,
+                    temperature,
+                    &strength,
                     report_moves,
                     policy,
                     value,
                     threads,
                     move_overhead,
+                    &opponent.profile,
                     &mut stored_message,
                 );
+
+                time_audit.extend(move_time);
             }
             "bench" => {
                 let depth = if let Some(d) = commands.get(1) {
@@ -80,7 +163,30 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork) {
                 bench(depth, policy, value, &params);
             }
             "perft" => run_perft(&commands, &pos),
-            "quit" => std::process::exit(0),
+            "testsuite" => {
+                // `testsuite <file> [movetime]`, 1 second per position by default
+                let (path, movetime) = match commands[1..] {
+                    [ref path @ .., time] if !path.is_empty() && time.parse::<u128>().is_ok() => {
+                        (path.join(" "), time.parse().unwrap())
+                    }
+                    ref path => (path.join(" "), 1000),
+                };
+
+                run_testsuite(
+                    &path, movetime, &mut tree, &params, &options, policy, value, threads,
+                );
+            }
+            "analyze" => {
+                let (path, limits) = analyze_args(&commands);
+                analyze(
+                    &path, limits, &mut tree, &params, &options, policy, value, threads,
+                );
+            }
+            "quit" => {
+                opponent.record_game(&game_moves, our_side);
+                report_time_audit(&time_audit);
+                std::process::exit(0);
+            }
             "eval" => {
                 println!("cp: {}", pos.get_value(value, &params));
                 println!("wdl: {:.2}%", 100.0 * pos.get_value_wdl(value, &params));
@@ -116,9 +222,48 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork) {
                 }
             }
             "d" => pos.display(policy),
-            "params" => params.list_spsa(),
+            "tree" => print_root_stats(&tree, options.cp_100_q),
+            "savetree" => {
+                if let Err(err) = tree.save(&commands[1..].join(" ")) {
+                    println!("info string failed to save tree: {err}");
+                }
+            }
+            // continue from the saved root, as if it had been set by `position`
+            "loadtree" => match tree.load(&commands[1..].join(" ")) {
+                Ok(()) => {
+                    pos = tree.root_position().clone();
+                    game_moves.clear();
+                }
+                Err(err) => println!("info string failed to load tree: {err}"),
+            },
+            "params" | "tune" => match commands[1..] {
+                ["save", ..] | ["load", ..] => {
+                    let path = commands.get(2).copied().unwrap_or("params.toml");
+
+                    let result = if commands[1] == "save" {
+                        fs::write(path, params.to_toml()).map_err(|err| err.to_string())
+                    } else {
+                        MctsParams::load(path).map(|loaded| params = loaded)
+                    };
+
+                    if let Err(err) = result {
+                        println!("info string failed to {} {path}: {err}", commands[1]);
+                    }
+                }
+                _ => params.list_spsa(),
+            },
+            "profile" => match commands[1..] {
+                ["reset"] => profile::reset(),
+                _ => profile::print_summary(),
+            },
+            "debug" => log::set_debug(commands.get(1) == Some(&"on")),
             "uci" => preamble(),
             "ucinewgame" => {
+                opponent.record_game(&game_moves, our_side);
+                game_moves.clear();
+                our_side = None;
+                report_time_audit(&time_audit);
+                time_audit.clear();
                 root_game_ply = 0;
                 tree.clear(threads);
             }
@@ -127,81 +272,84 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork) {
     }
 }
 
+/// Positions searched by `bench`.
+pub const BENCH_FENS: &[&str] = &[
+    "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14",
+    "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24",
+    "r3qbrk/6p1/2b2pPp/p3pP1Q/PpPpP2P/3P1B2/2PB3K/R5R1 w - - 16 42",
+    "6k1/1R3p2/6p1/2Bp3p/3P2q1/P7/1P2rQ1K/5R2 b - - 4 44",
+    "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54",
+    "7r/2p3k1/1p1p1qp1/1P1Bp3/p1P2r1P/P7/4R3/Q4RK1 w - - 0 36",
+    "r1bq1rk1/pp2b1pp/n1pp1n2/3P1p2/2P1p3/2N1P2N/PP2BPPP/R1BQ1RK1 b - - 2 10",
+    "3r3k/2r4p/1p1b3q/p4P2/P2Pp3/1B2P3/3BQ1RP/6K1 w - - 3 87",
+    "2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - 0 42",
+    "4q1bk/6b1/7p/p1p4p/PNPpP2P/KN4P1/3Q4/4R3 b - - 0 37",
+    "2q3r1/1r2pk2/pp3pp1/2pP3p/P1Pb1BbP/1P4Q1/R3NPP1/4R1K1 w - - 2 34",
+    "1r2r2k/1b4q1/pp5p/2pPp1p1/P3Pn2/1P1B1Q1P/2R3P1/4BR1K b - - 1 37",
+    "r3kbbr/pp1n1p1P/3ppnp1/q5N1/1P1pP3/P1N1B3/2P1QP2/R3KB1R b KQkq b3 0 17",
+    "8/6pk/2b1Rp2/3r4/1R1B2PP/P5K1/8/2r5 b - - 16 42",
+    "1r4k1/4ppb1/2n1b1qp/pB4p1/1n1BP1P1/7P/2PNQPK1/3RN3 w - - 8 29",
+    "8/p2B4/PkP5/4p1pK/4Pb1p/5P2/8/8 w - - 29 68",
+    "3r4/ppq1ppkp/4bnp1/2pN4/2P1P3/1P4P1/PQ3PBP/R4K2 b - - 2 20",
+    "5rr1/4n2k/4q2P/P1P2n2/3B1p2/4pP2/2N1P3/1RR1K2Q w - - 1 49",
+    "1r5k/2pq2p1/3p3p/p1pP4/4QP2/PP1R3P/6PK/8 w - - 1 51",
+    "q5k1/5ppp/1r3bn1/1B6/P1N2P2/BQ2P1P1/5K1P/8 b - - 2 34",
+    "r1b2k1r/5n2/p4q2/1ppn1Pp1/3pp1p1/NP2P3/P1PPBK2/1RQN2R1 w - - 0 22",
+    "r1bqk2r/pppp1ppp/5n2/4b3/4P3/P1N5/1PP2PPP/R1BQKB1R w KQkq - 0 5",
+    "r1bqr1k1/pp1p1ppp/2p5/8/3N1Q2/P2BB3/1PP2PPP/R3K2n b Q - 1 12",
+    "r1bq2k1/p4r1p/1pp2pp1/3p4/1P1B3Q/P2B1N2/2P3PP/4R1K1 b - - 2 19",
+    "r4qk1/6r1/1p4p1/2ppBbN1/1p5Q/P7/2P3PP/5RK1 w - - 2 25",
+    "r7/6k1/1p6/2pp1p2/7Q/8/p1P2K1P/8 w - - 0 32",
+    "r3k2r/ppp1pp1p/2nqb1pn/3p4/4P3/2PP4/PP1NBPPP/R2QK1NR w KQkq - 1 5",
+    "3r1rk1/1pp1pn1p/p1n1q1p1/3p4/Q3P3/2P5/PP1NBPPP/4RRK1 w - - 0 12",
+    "5rk1/1pp1pn1p/p3Brp1/8/1n6/5N2/PP3PPP/2R2RK1 w - - 2 20",
+    "8/1p2pk1p/p1p1r1p1/3n4/8/5R2/PP3PPP/4R1K1 b - - 3 27",
+    "8/4pk2/1p1r2p1/p1p4p/Pn5P/3R4/1P3PP1/4RK2 w - - 1 33",
+    "8/5k2/1pnrp1p1/p1p4p/P6P/4R1PK/1P3P2/4R3 b - - 1 38",
+    "8/8/1p1kp1p1/p1pr1n1p/P6P/1R4P1/1P3PK1/1R6 b - - 15 45",
+    "8/8/1p1k2p1/p1prp2p/P2n3P/6P1/1P1R1PK1/4R3 b - - 5 49",
+    "8/8/1p4p1/p1p2k1p/P2npP1P/4K1P1/1P6/3R4 w - - 6 54",
+    "8/8/1p4p1/p1p2k1p/P2n1P1P/4K1P1/1P6/6R1 b - - 6 59",
+    "8/5k2/1p4p1/p1pK3p/P2n1P1P/6P1/1P6/4R3 b - - 14 63",
+    "8/1R6/1p1K1kp1/p6p/P1p2P1P/6P1/1Pn5/8 w - - 0 67",
+    "1rb1rn1k/p3q1bp/2p3p1/2p1p3/2P1P2N/PP1RQNP1/1B3P2/4R1K1 b - - 4 23",
+    "4rrk1/pp1n1pp1/q5p1/P1pP4/2n3P1/7P/1P3PB1/R1BQ1RK1 w - - 3 22",
+    "r2qr1k1/pb1nbppp/1pn1p3/2ppP3/3P4/2PB1NN1/PP3PPP/R1BQR1K1 w - - 4 12",
+    "2r2k2/8/4P1R1/1p6/8/P4K1N/7b/2B5 b - - 0 55",
+    "6k1/5pp1/8/2bKP2P/2P5/p4PNb/B7/8 b - - 1 44",
+    "2rqr1k1/1p3p1p/p2p2p1/P1nPb3/2B1P3/5P2/1PQ2NPP/R1R4K w - - 3 25",
+    "r1b2rk1/p1q1ppbp/6p1/2Q5/8/4BP2/PPP3PP/2KR1B1R b - - 2 14",
+    "6r1/5k2/p1b1r2p/1pB1p1p1/1Pp3PP/2P1R1K1/2P2P2/3R4 w - - 1 36",
+    "rnbqkb1r/pppppppp/5n2/8/2PP4/8/PP2PPPP/RNBQKBNR b KQkq c3 0 2",
+    "2rr2k1/1p4bp/p1q1p1p1/4Pp1n/2PB4/1PN3P1/P3Q2P/2RR2K1 w - f6 0 20",
+    "3br1k1/p1pn3p/1p3n2/5pNq/2P1p3/1PN3PP/P2Q1PB1/4R1K1 w - - 0 23",
+    "2r2b2/5p2/5k2/p1r1pP2/P2pB3/1P3P2/K1P3R1/7R w - - 23 93",
+    "5k2/4q1p1/3P1pQb/1p1B4/pP5p/P1PR4/5PP1/1K6 b - - 0 38",
+    "5rk1/1rP3pp/p4n2/3Pp3/1P2Pq2/2Q4P/P5P1/R3R1K1 b - - 0 32",
+    "4r1k1/4r1p1/8/p2R1P1K/5P1P/1QP3q1/1P6/3R4 b - - 0 1",
+    "3qk1b1/1p4r1/1n4r1/2P1b2B/p3N2p/P2Q3P/8/1R3R1K w - - 2 39",
+];
+
 pub fn bench(depth: usize, policy: &PolicyNetwork, value: &ValueNetwork, params: &MctsParams) {
     let mut total_nodes = 0;
     let mut time = 0.0;
 
-    let bench_fens = [
-        "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14",
-        "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24",
-        "r3qbrk/6p1/2b2pPp/p3pP1Q/PpPpP2P/3P1B2/2PB3K/R5R1 w - - 16 42",
-        "6k1/1R3p2/6p1/2Bp3p/3P2q1/P7/1P2rQ1K/5R2 b - - 4 44",
-        "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54",
-        "7r/2p3k1/1p1p1qp1/1P1Bp3/p1P2r1P/P7/4R3/Q4RK1 w - - 0 36",
-        "r1bq1rk1/pp2b1pp/n1pp1n2/3P1p2/2P1p3/2N1P2N/PP2BPPP/R1BQ1RK1 b - - 2 10",
-        "3r3k/2r4p/1p1b3q/p4P2/P2Pp3/1B2P3/3BQ1RP/6K1 w - - 3 87",
-        "2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - 0 42",
-        "4q1bk/6b1/7p/p1p4p/PNPpP2P/KN4P1/3Q4/4R3 b - - 0 37",
-        "2q3r1/1r2pk2/pp3pp1/2pP3p/P1Pb1BbP/1P4Q1/R3NPP1/4R1K1 w - - 2 34",
-        "1r2r2k/1b4q1/pp5p/2pPp1p1/P3Pn2/1P1B1Q1P/2R3P1/4BR1K b - - 1 37",
-        "r3kbbr/pp1n1p1P/3ppnp1/q5N1/1P1pP3/P1N1B3/2P1QP2/R3KB1R b KQkq b3 0 17",
-        "8/6pk/2b1Rp2/3r4/1R1B2PP/P5K1/8/2r5 b - - 16 42",
-        "1r4k1/4ppb1/2n1b1qp/pB4p1/1n1BP1P1/7P/2PNQPK1/3RN3 w - - 8 29",
-        "8/p2B4/PkP5/4p1pK/4Pb1p/5P2/8/8 w - - 29 68",
-        "3r4/ppq1ppkp/4bnp1/2pN4/2P1P3/1P4P1/PQ3PBP/R4K2 b - - 2 20",
-        "5rr1/4n2k/4q2P/P1P2n2/3B1p2/4pP2/2N1P3/1RR1K2Q w - - 1 49",
-        "1r5k/2pq2p1/3p3p/p1pP4/4QP2/PP1R3P/6PK/8 w - - 1 51",
-        "q5k1/5ppp/1r3bn1/1B6/P1N2P2/BQ2P1P1/5K1P/8 b - - 2 34",
-        "r1b2k1r/5n2/p4q2/1ppn1Pp1/3pp1p1/NP2P3/P1PPBK2/1RQN2R1 w - - 0 22",
-        "r1bqk2r/pppp1ppp/5n2/4b3/4P3/P1N5/1PP2PPP/R1BQKB1R w KQkq - 0 5",
-        "r1bqr1k1/pp1p1ppp/2p5/8/3N1Q2/P2BB3/1PP2PPP/R3K2n b Q - 1 12",
-        "r1bq2k1/p4r1p/1pp2pp1/3p4/1P1B3Q/P2B1N2/2P3PP/4R1K1 b - - 2 19",
-        "r4qk1/6r1/1p4p1/2ppBbN1/1p5Q/P7/2P3PP/5RK1 w - - 2 25",
-        "r7/6k1/1p6/2pp1p2/7Q/8/p1P2K1P/8 w - - 0 32",
-        "r3k2r/ppp1pp1p/2nqb1pn/3p4/4P3/2PP4/PP1NBPPP/R2QK1NR w KQkq - 1 5",
-        "3r1rk1/1pp1pn1p/p1n1q1p1/3p4/Q3P3/2P5/PP1NBPPP/4RRK1 w - - 0 12",
-        "5rk1/1pp1pn1p/p3Brp1/8/1n6/5N2/PP3PPP/2R2RK1 w - - 2 20",
-        "8/1p2pk1p/p1p1r1p1/3n4/8/5R2/PP3PPP/4R1K1 b - - 3 27",
-        "8/4pk2/1p1r2p1/p1p4p/Pn5P/3R4/1P3PP1/4RK2 w - - 1 33",
-        "8/5k2/1pnrp1p1/p1p4p/P6P/4R1PK/1P3P2/4R3 b - - 1 38",
-        "8/8/1p1kp1p1/p1pr1n1p/P6P/1R4P1/1P3PK1/1R6 b - - 15 45",
-        "8/8/1p1k2p1/p1prp2p/P2n3P/6P1/1P1R1PK1/4R3 b - - 5 49",
-        "8/8/1p4p1/p1p2k1p/P2npP1P/4K1P1/1P6/3R4 w - - 6 54",
-        "8/8/1p4p1/p1p2k1p/P2n1P1P/4K1P1/1P6/6R1 b - - 6 59",
-        "8/5k2/1p4p1/p1pK3p/P2n1P1P/6P1/1P6/4R3 b - - 14 63",
-        "8/1R6/1p1K1kp1/p6p/P1p2P1P/6P1/1Pn5/8 w - - 0 67",
-        "1rb1rn1k/p3q1bp/2p3p1/2p1p3/2P1P2N/PP1RQNP1/1B3P2/4R1K1 b - - 4 23",
-        "4rrk1/pp1n1pp1/q5p1/P1pP4/2n3P1/7P/1P3PB1/R1BQ1RK1 w - - 3 22",
-        "r2qr1k1/pb1nbppp/1pn1p3/2ppP3/3P4/2PB1NN1/PP3PPP/R1BQR1K1 w - - 4 12",
-        "2r2k2/8/4P1R1/1p6/8/P4K1N/7b/2B5 b - - 0 55",
-        "6k1/5pp1/8/2bKP2P/2P5/p4PNb/B7/8 b - - 1 44",
-        "2rqr1k1/1p3p1p/p2p2p1/P1nPb3/2B1P3/5P2/1PQ2NPP/R1R4K w - - 3 25",
-        "r1b2rk1/p1q1ppbp/6p1/2Q5/8/4BP2/PPP3PP/2KR1B1R b - - 2 14",
-        "6r1/5k2/p1b1r2p/1pB1p1p1/1Pp3PP/2P1R1K1/2P2P2/3R4 w - - 1 36",
-        "rnbqkb1r/pppppppp/5n2/8/2PP4/8/PP2PPPP/RNBQKBNR b KQkq c3 0 2",
-        "2rr2k1/1p4bp/p1q1p1p1/4Pp1n/2PB4/1PN3P1/P3Q2P/2RR2K1 w - f6 0 20",
-        "3br1k1/p1pn3p/1p3n2/5pNq/2P1p3/1PN3PP/P2Q1PB1/4R1K1 w - - 0 23",
-        "2r2b2/5p2/5k2/p1r1pP2/P2pB3/1P3P2/K1P3R1/7R w - - 23 93",
-        "5k2/4q1p1/3P1pQb/1p1B4/pP5p/P1PR4/5PP1/1K6 b - - 0 38",
-        "5rk1/1rP3pp/p4n2/3Pp3/1P2Pq2/2Q4P/P5P1/R3R1K1 b - - 0 32",
-        "4r1k1/4r1p1/8/p2R1P1K/5P1P/1QP3q1/1P6/3R4 b - - 0 1",
-        "3qk1b1/1p4r1/1n4r1/2P1b2B/p3N2p/P2Q3P/8/1R3R1K w - - 2 39",
-    ];
-
     let limits = Limits {
         max_time: None,
         opt_time: None,
         max_depth: depth,
         max_nodes: 1_000_000,
+        max_mate: None,
     };
 
     let mut tree = Tree::new_mb(32, 1);
+    let options = SearchOptions::default();
 
-    for fen in bench_fens {
+    for fen in BENCH_FENS {
         let abort = AtomicBool::new(false);
         let pos = ChessState::from_fen(fen);
         tree.set_root_position(&pos);
-        let searcher = Searcher::new(&tree, params, policy, value, &abort);
+        let searcher = Searcher::new(&tree, params, &options, policy, value, &abort);
         let timer = Instant::now();
         searcher.search(1, limits, false, &mut total_nodes);
         time += timer.elapsed().as_secs_f32();
@@ -219,8 +367,40 @@ fn preamble() {
     println!("id author Jamie Whiting, Viren & The Monty Authors");
     println!("option name Hash type spin default 64 min 1 max 8192");
     println!("option name Threads type spin default 1 min 1 max 512");
+    println!("option name LargePages type check default false");
     println!("option name UCI_Chess960 type check default false");
-    println!("option name MoveOverhead type spin default 40 min 0 max 5000");
+    println!("option name UCI_LimitStrength type check default false");
+    println!(
+        "option name UCI_Elo type spin default {ENGINE_ELO} min {} max {ENGINE_ELO}",
+        Strength::MIN_ELO
+    );
+    println!("option name Move Overhead type spin default 40 min 0 max 5000");
+    println!("option name Priors type combo default network var network var heuristic var uniform");
+    println!("option name RootSelection type combo default puct var puct var gumbel");
+    println!("option name ProgressiveWidening type check default false");
+    println!("option name ReproSMP type check default false");
+    println!("option name RoundRobinSMP type check default false");
+    println!("option name Contempt type spin default 0 min -100 max 100");
+    println!("option name ScoreNormalization type spin default 607 min 510 max 900");
+    println!("option name RiskAppetite type spin default 0 min -100 max 100");
+    println!("option name Temperature type spin default 0 min 0 max 200");
+    println!("option name TemperatureDecayPlies type spin default 0 min 0 max 500");
+    println!(
+        "option name UCI_EngineAbout type string default {} by Jamie Whiting, Viren & The Monty Authors, an MCTS chess engine",
+        env!("FORMATTED_NAME")
+    );
+    println!("option name MinimumKLDGainPerNode type string default 0");
+    println!("option name KLDGainAverageInterval type spin default 100 min 1 max 100000");
+    println!("option name MaxDepth type spin default 0 min 0 max 256");
+    println!("option name ReportInterval type spin default 0 min 0 max 60000");
+    println!("option name ReuseStats type check default false");
+    println!("option name SANOutput type check default false");
+    println!("option name OutputFormat type combo default uci var uci var json");
+    println!("option name OwnBookPlies type spin default 0 min 0 max 20");
+    println!("option name UCI_Opponent type string default");
+    println!("option name AutoContempt type check default false");
+    println!("option name OpponentProfileDir type string default");
+    println!("option name LogFile type string default");
     println!("option name report_moves type button");
 
     #[cfg(feature = "tunable")]
@@ -229,47 +409,206 @@ fn preamble() {
     println!("uciok");
 }
 
+#[allow(clippy::too_many_arguments)]
 fn setoption(
     commands: &[&str],
     params: &mut MctsParams,
+    options: &mut SearchOptions,
     report_moves: &mut bool,
     tree: &mut Tree,
     threads: &mut usize,
     move_overhead: &mut usize,
+    own_book_plies: &mut usize,
+    opponent: &mut OpponentInfo,
+    strength: &mut Strength,
 ) {
-    if let ["setoption", "name", "report_moves"] = commands {
+    // option names and values may contain spaces,
+    // e.g. `setoption name Move Overhead value 100`
+    let (name, value) = match commands {
+        ["setoption", "name", rest @ ..] => match rest.iter().position(|&cmd| cmd == "value") {
+            Some(idx) => (rest[..idx].join(" "), Some(rest[idx + 1..].join(" "))),
+            None => (rest.join(" "), None),
+        },
+        _ => return,
+    };
+
+    if name == "report_moves" {
         *report_moves = !*report_moves;
         return;
     }
 
-    let (name, val) = if let ["setoption", "name", x, "value", y] = commands {
-        if *x == "UCI_Chess960" {
-            return;
+    let Some(y) = value.as_deref() else {
+        return;
+    };
+
+    match name.as_str() {
+        "UCI_Chess960" => {}
+        "UCI_LimitStrength" => strength.limit = y == "true",
+        "UCI_Elo" => strength.set_elo(y.parse().unwrap_or(ENGINE_ELO)),
+        // search threads are spawned per search, and clearing is split
+        // by the thread count at the time, so only the pool needs resizing
+        "Threads" => {
+            *threads = y.parse().map_or(*threads, |t: usize| t.clamp(1, 512));
+            pool::resize(*threads);
+        }
+        // `MoveOverhead` kept for compatibility with existing configs
+        "Move Overhead" | "MoveOverhead" => {
+            *move_overhead = y.parse().map_or(*move_overhead, |t: usize| t.min(5000));
+        }
+        "Priors" => {
+            options.priors = match y {
+                "heuristic" => Priors::Heuristic,
+                "uniform" => Priors::Uniform,
+                _ => Priors::Network,
+            };
+        }
+        "RootSelection" => {
+            options.root_selection = match y {
+                "gumbel" => RootSelection::Gumbel,
+                _ => RootSelection::Puct,
+            };
+        }
+        "ProgressiveWidening" => options.progressive_widening = y == "true",
+        "ReproSMP" => options.repro_smp = y == "true",
+        "RoundRobinSMP" => options.round_robin_smp = y == "true",
+        "Contempt" => {
+            let cp = y.parse::<i32>().unwrap_or(0).clamp(-100, 100);
+            options.contempt = score::eval_cp_to_q(cp, score::EVAL_SCALE) - 0.5;
+        }
+        // Q reported as 100 cp, in thousandths
+        "ScoreNormalization" => {
+            let q = y.parse::<u32>().unwrap_or(607).clamp(510, 900);
+            options.cp_100_q = q as f32 / 1000.0;
+        }
+        // in hundredths of a standard deviation
+        "RiskAppetite" => {
+            let risk = y.parse::<i32>().unwrap_or(0).clamp(-100, 100);
+            options.risk = risk as f32 / 100.0;
+        }
+        // in hundredths, e.g. 100 to sample in proportion to visits
+        "Temperature" => {
+            let temperature = y.parse::<u32>().unwrap_or(0).min(200);
+            options.temperature = temperature as f32 / 100.0;
+        }
+        "TemperatureDecayPlies" => {
+            options.temperature_decay_plies = y.parse::<u32>().unwrap_or(0).min(500);
+        }
+        "MinimumKLDGainPerNode" => {
+            options.min_kld_gain = y.parse::<f32>().unwrap_or(0.0).max(0.0);
+        }
+        "KLDGainAverageInterval" => {
+            options.kld_gain_interval = y.parse::<usize>().unwrap_or(100).clamp(1, 100_000);
+        }
+        // 0 for no limit
+        "MaxDepth" => {
+            let depth = y.parse::<usize>().unwrap_or(0).min(256);
+            options.max_tree_depth = (depth > 0).then_some(depth);
+        }
+        "ReportInterval" => {
+            options.report_interval = y.parse::<u64>().unwrap_or(0).min(60000);
+        }
+        // 0 to always search
+        "OwnBookPlies" => *own_book_plies = y.parse::<usize>().unwrap_or(0).min(20),
+        "ReuseStats" => options.reuse_stats = y == "true",
+        "SANOutput" => options.san_output = y == "true",
+        "OutputFormat" => {
+            options.output_format = match y {
+                "json" => OutputFormat::Json,
+                _ => OutputFormat::Uci,
+            };
         }
+        "UCI_Opponent" => {
+            opponent.opponent = Opponent::parse(y);
+            opponent.load_profile();
+            opponent.apply_hook(options);
+        }
+        "AutoContempt" => {
+            opponent.auto_contempt = y == "true";
+            opponent.apply_hook(options);
+        }
+        // purely informational
+        "UCI_EngineAbout" => {}
+        "OpponentProfileDir" => {
+            opponent.profile_dir = (!y.is_empty()).then(|| y.to_string());
+            opponent.load_profile();
+        }
+        "LogFile" => {
+            if let Err(err) = log::set_file(y) {
+                println!("info string failed to open log file {y}: {err}");
+            }
+        }
+        // reallocates, as huge pages can only be asked for before the
+        // memory is first written
+        "LargePages" => {
+            tree::set_large_pages(y == "true");
+            *tree = Tree::new_mb(tree.mb(), *threads);
+        }
+        "Hash" => {
+            let timer = Instant::now();
+            *tree = Tree::new_mb(y.parse().unwrap_or(0), *threads);
+            log!(
+                Info,
+                "hash",
+                "allocation took {} ms using {} workers",
+                timer.elapsed().as_millis(),
+                pool::stats().workers,
+            );
+        }
+        // tuners may send values that aren't whole numbers
+        _ => match y.parse::<f64>() {
+            Ok(val) => params.set(&name, val.round() as i32),
+            Err(_) => println!("info string invalid value {y} for {name}"),
+        },
+    }
+}
 
-        if *x == "Threads" {
-            *threads = y.parse().unwrap();
-            return;
+/// What is known about the opponent in the current game.
+struct OpponentInfo {
+    opponent: Option<Opponent>,
+    profile_dir: Option<String>,
+    profile: OpponentProfile,
+    auto_contempt: bool,
+    hook: OpponentHook,
+}
+
+impl OpponentInfo {
+    fn apply_hook(&self, options: &mut SearchOptions) {
+        if let (true, Some(opponent)) = (self.auto_contempt, &self.opponent) {
+            (self.hook)(opponent, options);
         }
+    }
+
+    fn load_profile(&mut self) {
+        self.profile = match (&self.profile_dir, &self.opponent) {
+            (Some(dir), Some(opponent)) => OpponentProfile::load(dir, opponent),
+            _ => OpponentProfile::default(),
+        };
+    }
+
+    /// Record the moves the opponent played in the game, given as the
+    /// position hash, move and side to move of each ply.
+    fn record_game(&mut self, moves: &[(u64, Move, usize)], our_side: Option<usize>) {
+        let Some(our_side) = our_side else {
+            return;
+        };
 
-        if *x == "MoveOverhead" {
-            *move_overhead = y.parse().unwrap();
+        if !self.profile.is_active() {
             return;
         }
 
-        (*x, y.parse::<i32>().unwrap_or(0))
-    } else {
-        return;
-    };
+        for &(hash, mov, side) in moves {
+            if side != our_side {
+                self.profile.record(hash, mov);
+            }
+        }
 
-    if name == "Hash" {
-        *tree = Tree::new_mb(val as usize, *threads);
-    } else {
-        params.set(name, val);
+        if let Err(err) = self.profile.save() {
+            println!("info string failed to save opponent profile: {err}");
+        }
     }
 }
 
-fn position(commands: Vec<&str>, pos: &mut ChessState) {
+fn position(commands: Vec<&str>, pos: &mut ChessState, game_moves: &mut Vec<(u64, Move, usize)>) {
     let mut fen = String::new();
     let mut move_list = Vec::new();
     let mut moves = false;
@@ -289,21 +628,52 @@ fn position(commands: Vec<&str>, pos: &mut ChessState) {
         }
     }
 
-    *pos = ChessState::from_fen(&fen);
+    // keep the previous position rather than searching a garbage one
+    *pos = match ChessState::try_from_fen(&fen) {
+        Ok(new_pos) => new_pos,
+        Err(err) => {
+            println!("info string invalid fen '{}': {err}", fen.trim());
+            return;
+        }
+    };
 
-    for &m in move_list.iter() {
-        let mut this_mov = Move::default();
+    game_moves.clear();
 
-        pos.map_legal_moves(|mov| {
-            if m == pos.conv_mov_to_str(mov) {
-                this_mov = mov;
-            }
-        });
+    for &m in move_list.iter() {
+        // also accept SAN, for scripts
+        let Some(this_mov) = pos.parse_uci(m).or_else(|| pos.parse_san(m)) else {
+            println!("info string illegal move {m}, ignoring the rest of the moves");
+            return;
+        };
 
+        game_moves.push((pos.hash(), this_mov, pos.stm()));
         pos.make_move(this_mov);
     }
 }
 
+/// A move from the built-in opening lines, if the game started from the
+/// starting position and is still in one of them within `plies`.
+fn book_move(pos: &ChessState, game_moves: &[(u64, Move, usize)], plies: usize) -> Option<Move> {
+    let first_hash = game_moves.first().map_or(pos.hash(), |&(hash, _, _)| hash);
+
+    if game_moves.len() >= plies || first_hash != ChessState::default().hash() {
+        return None;
+    }
+
+    let moves = game_moves
+        .iter()
+        .map(|&(_, mov, _)| mov)
+        .collect::<Vec<_>>();
+    let next = openings::continuations(&openings::played_moves(&moves));
+
+    if next.is_empty() {
+        return None;
+    }
+
+    let idx = (Rng::new().next_f32() * next.len() as f32) as usize;
+    pos.parse_uci(next[idx.min(next.len() - 1)])
+}
+
 #[allow(clippy::too_many_arguments)]
 fn go(
     commands: &[&str],
@@ -311,16 +681,24 @@ fn go(
     pos: &ChessState,
     root_game_ply: u32,
     params: &MctsParams,
+    options: &SearchOptions,
+    temperature: f32,
+    strength: &Strength,
     report_moves: bool,
     policy: &PolicyNetwork,
     value: &ValueNetwork,
     threads: usize,
     move_overhead: usize,
+    profile: &OpponentProfile,
     stored_message: &mut Option<String>,
-) {
+) -> Option<MoveTime> {
+    let timer = Instant::now();
+
     let mut max_nodes = i32::MAX as usize;
     let mut max_time = None;
     let mut max_depth = 256;
+    let mut max_mate = None;
+    let mut search_moves = Vec::new();
 
     let mut times = [None; 2];
     let mut incs = [None; 2];
@@ -336,6 +714,8 @@ fn go(
             "nodes" => mode = "nodes",
             "movetime" => mode = "movetime",
             "depth" => mode = "depth",
+            "mate" => mode = "mate",
+            "searchmoves" => mode = "searchmoves",
             "wtime" => mode = "wtime",
             "btime" => mode = "btime",
             "winc" => mode = "winc",
@@ -345,6 +725,12 @@ fn go(
                 "nodes" => max_nodes = cmd.parse().unwrap_or(max_nodes),
                 "movetime" => max_time = cmd.parse().ok(),
                 "depth" => max_depth = cmd.parse().unwrap_or(max_depth),
+                "mate" => max_mate = cmd.parse().ok(),
+                "searchmoves" => pos.map_legal_moves(|mov| {
+                    if *cmd == pos.conv_mov_to_str(mov) {
+                        search_moves.push(mov);
+                    }
+                }),
                 "wtime" => times[0] = saturating_parse(cmd),
                 "btime" => times[1] = saturating_parse(cmd),
                 "winc" => incs[0] = saturating_parse(cmd),
@@ -355,45 +741,57 @@ fn go(
         }
     }
 
+    // `go movetime <time>`, less the move overhead
+    max_time = max_time.map(|t: u128| t.saturating_sub(move_overhead as u128));
+
     // `go wtime <wtime> btime <btime> winc <winc> binc <binc>``
     if let Some(remaining) = times[pos.stm()] {
-        let timeman =
-            SearchHelpers::get_time(remaining, incs[pos.stm()], root_game_ply, movestogo, params);
+        let timeman = SearchHelpers::get_time(
+            remaining,
+            incs[pos.stm()],
+            root_game_ply,
+            movestogo,
+            move_overhead as u64,
+            params,
+        );
 
+        // if both movetime and a clock are given, use the lower of the two
         opt_time = Some(timeman.0);
-        max_time = Some(timeman.1);
-    }
-
-    // `go movetime <time>`
-    if let Some(max) = max_time {
-        // if both movetime and increment time controls given, use
-        max_time = Some(max_time.unwrap_or(u128::MAX).min(max));
-    }
-
-    // apply move overhead
-    if let Some(t) = opt_time.as_mut() {
-        *t = t.saturating_sub(move_overhead as u128);
-    }
-    if let Some(t) = max_time.as_mut() {
-        *t = t.saturating_sub(move_overhead as u128);
+        max_time = Some(max_time.map_or(timeman.1, |t| t.min(timeman.1)));
     }
 
     let abort = AtomicBool::new(false);
 
     tree.set_root_position(pos);
+    tree.set_root_moves(search_moves);
+    tree.set_reply_boost(profile.reply_frequencies(pos));
 
-    let limits = Limits {
+    let limits = strength.limits(Limits {
         max_time,
         opt_time,
         max_depth,
         max_nodes,
-    };
+        max_mate,
+    });
+
+    let params = &strength.params(params);
+    let mut spent = 0;
 
     std::thread::scope(|s| {
         s.spawn(|| {
-            let searcher = Searcher::new(tree, params, policy, value, &abort);
-            let (mov, _) = searcher.search(threads, limits, true, &mut 0);
-            println!("bestmove {}", pos.conv_mov_to_str(mov));
+            let searcher = Searcher::new(tree, params, options, policy, value, &abort);
+            let result = searcher.search(threads, limits, true, &mut 0);
+            let mut rng = Rng::new();
+            let mov = strength.choose_move(&result, temperature, || rng.next_f32());
+            let mov = pos.conv_mov_to_str(mov);
+
+            if options.output_format == OutputFormat::Json {
+                println!("{{\"bestmove\":\"{mov}\"}}");
+            } else {
+                println!("bestmove {mov}");
+            }
+
+            spent = timer.elapsed().as_millis();
 
             if report_moves {
                 searcher.display_moves();
@@ -402,6 +800,354 @@ fn go(
 
         *stored_message = handle_search_input(&abort);
     });
+
+    // only moves played on a clock are audited
+    Some(MoveTime {
+        remaining: times[pos.stm()]?,
+        opt: opt_time?,
+        max: max_time?,
+        spent,
+    })
+}
+
+/// Time allocated to and spent on a move played on a clock.
+struct MoveTime {
+    remaining: u64,
+    opt: u128,
+    max: u128,
+    spent: u128,
+}
+
+/// Prints the time allocated to and spent on each move of the game,
+/// to help diagnose time losses.
+fn report_time_audit(moves: &[MoveTime]) {
+    if moves.is_empty() {
+        return;
+    }
+
+    for (i, mt) in moves.iter().enumerate() {
+        log!(
+            Info,
+            "time audit",
+            "move {} remaining {} opt {} max {} spent {}",
+            i + 1,
+            mt.remaining,
+            mt.opt,
+            mt.max,
+            mt.spent,
+        );
+    }
+
+    let spent = moves.iter().map(|mt| mt.spent).sum::<u128>();
+    let opt = moves.iter().map(|mt| mt.opt).sum::<u128>();
+    let over_opt = moves.iter().filter(|mt| mt.spent > mt.opt).count();
+    let over_max = moves.iter().filter(|mt| mt.spent > mt.max).count();
+    let lowest = moves.iter().map(|mt| mt.remaining).min().unwrap_or(0);
+
+    log!(
+        Info,
+        "time audit",
+        "{} moves spent {spent} ms (opt {opt} ms) over opt {over_opt} over max {over_max} lowest clock {lowest} ms",
+        moves.len(),
+    );
+}
+
+/// Statistics of every root move of the last search, most visited first
+/// (`tree` command).
+fn print_root_stats(tree: &Tree, cp_100_q: f32) {
+    if tree.is_empty() {
+        println!("info string no tree");
+        return;
+    }
+
+    let root = tree.root_node();
+    let pos = tree.root_position();
+    let (first_child_ptr, num_actions) = tree[root].children();
+
+    let mut children = (0..num_actions)
+        .map(|action| first_child_ptr + action)
+        .collect::<Vec<_>>();
+
+    children.sort_by_key(|&ptr| std::cmp::Reverse(tree[ptr].visits()));
+
+    for ptr in children {
+        let child = &tree[ptr];
+        let mov = child.parent_move();
+        let score = Score::from_child(child.state(), child.q());
+
+        print!(
+            "{:<6} ({:<7}) visits {:<9} q {:>6.2}% policy {:>6.2}% var {:.4} score {} pv",
+            pos.conv_mov_to_str(mov),
+            pos.conv_mov_to_san(mov),
+            child.visits(),
+            child.q() * 100.0,
+            child.policy() * 100.0,
+            child.var(),
+            score.to_uci(cp_100_q),
+        );
+
+        let mut line_pos = pos.clone();
+        let mut pv_ptr = ptr;
+
+        while !pv_ptr.is_null() && tree[pv_ptr].visits() > 0 {
+            let mov = tree[pv_ptr].parent_move();
+            print!(" {}", line_pos.conv_mov_to_str(mov));
+            line_pos.make_move(mov);
+
+            let idx = tree.get_best_child(pv_ptr);
+            if idx == usize::MAX {
+                break;
+            }
+
+            pv_ptr = tree[pv_ptr].actions() + idx;
+        }
+
+        println!();
+    }
+}
+
+/// Parses `analyze <file> [nodes <n>] [movetime <ms>]`, searching
+/// 10000 nodes per position by default.
+fn analyze_args(commands: &[&str]) -> (String, Limits) {
+    let mut limits = Limits::nodes(10_000);
+
+    let end = commands
+        .iter()
+        .position(|&cmd| cmd == "nodes" || cmd == "movetime")
+        .unwrap_or(commands.len());
+
+    for pair in commands[end..].chunks(2) {
+        match pair {
+            ["nodes", n] => limits.max_nodes = n.parse().unwrap_or(limits.max_nodes),
+            ["movetime", ms] => limits.max_time = ms.parse().ok(),
+            _ => {}
+        }
+    }
+
+    (commands[1..end].join(" "), limits)
+}
+
+/// Searches every position of the games in the PGN file at `path`, and
+/// prints the games again with the evaluation (from white's perspective)
+/// and best move of each position as a comment on the move played from it.
+#[allow(clippy::too_many_arguments)]
+pub fn analyze(
+    path: &str,
+    limits: Limits,
+    tree: &mut Tree,
+    params: &MctsParams,
+    options: &SearchOptions,
+    policy: &PolicyNetwork,
+    value: &ValueNetwork,
+    threads: usize,
+) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            println!("info string failed to read {path}: {err}");
+            return;
+        }
+    };
+
+    for (idx, game) in parse_pgn(&text).into_iter().enumerate() {
+        let game = match game {
+            Ok(game) => game,
+            Err(err) => {
+                println!("info string skipping game {}: {err}", idx + 1);
+                continue;
+            }
+        };
+
+        tree.clear(threads);
+
+        for (tag, value) in &game.tags {
+            println!("[{tag} \"{}\"]", value.replace('"', "\\\""));
+        }
+
+        println!();
+
+        let mut pos = game.start.clone();
+        let mut fullmove = pos
+            .as_fen()
+            .split_whitespace()
+            .nth(5)
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or(1);
+
+        let mut tokens = Vec::new();
+
+        for &mov in &game.moves {
+            // a fresh tree for each position, so that each gets the same
+            // budget (and without reuse messages mixed into the output)
+            let abort = AtomicBool::new(false);
+            tree.clear_halves();
+            tree.set_root_position(&pos);
+
+            let searcher = Searcher::new(tree, params, options, policy, value, &abort);
+            let result = searcher.search(threads, limits, false, &mut 0);
+
+            let white = pos.stm() == 0;
+            let score = result
+                .variations(1)
+                .first()
+                .map_or(Score::Q(result.q), |v| v.score);
+            let eval = match (score, white) {
+                (Score::Mate(n), true) => format!("#{n}"),
+                (Score::Mate(n), false) => format!("#{}", -n),
+                (score, true) => format!("{:.2}", score.cp_with(options.cp_100_q) / 100.0),
+                (score, false) => format!("{:.2}", -score.cp_with(options.cp_100_q) / 100.0),
+            };
+
+            tokens.push(if white {
+                format!("{fullmove}.")
+            } else {
+                format!("{fullmove}...")
+            });
+
+            tokens.push(pos.conv_mov_to_san(mov));
+            tokens.push(format!(
+                "{{[%eval {eval}] best {}}}",
+                pos.conv_mov_to_san(result.best_move)
+            ));
+
+            if !white {
+                fullmove += 1;
+            }
+
+            pos.make_move(mov);
+        }
+
+        tokens.push(game.result.clone());
+
+        let mut line = String::new();
+
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                println!("{line}");
+                line.clear();
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+
+            line.push_str(&token);
+        }
+
+        println!("{line}");
+        println!();
+    }
+}
+
+/// Searches each position of the EPD file at `path` for `movetime` ms,
+/// reporting whether the best move was one of its `bm` moves (and none of
+/// its `am` moves), and the points for it in suites scored like STS.
+#[allow(clippy::too_many_arguments)]
+fn run_testsuite(
+    path: &str,
+    movetime: u128,
+    tree: &mut Tree,
+    params: &MctsParams,
+    options: &SearchOptions,
+    policy: &PolicyNetwork,
+    value: &ValueNetwork,
+    threads: usize,
+) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            println!("info string failed to read {path}: {err}");
+            return;
+        }
+    };
+
+    let limits = Limits::movetime(movetime);
+
+    let mut total = 0;
+    let mut solved = 0;
+    let mut solution_time = 0;
+    let mut points = 0;
+    let mut max_points = 0;
+
+    tree.clear(threads);
+
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = match EpdRecord::parse(line) {
+            Ok(record) => record,
+            Err(err) => {
+                println!("info string skipping line {}: {err}", idx + 1);
+                continue;
+            }
+        };
+
+        let best_moves = record.moves("bm");
+        let avoid_moves = record.moves("am");
+        let move_points = record.move_points();
+
+        if best_moves.is_empty() && avoid_moves.is_empty() {
+            println!("info string skipping line {}: no bm or am", idx + 1);
+            continue;
+        }
+
+        let abort = AtomicBool::new(false);
+        tree.clear_halves();
+        tree.set_root_position(&record.pos);
+
+        let searcher = Searcher::new(tree, params, options, policy, value, &abort);
+        let result = searcher.search(threads, limits, false, &mut 0);
+        let mov = result.best_move;
+
+        let passed =
+            (best_moves.is_empty() || best_moves.contains(&mov)) && !avoid_moves.contains(&mov);
+
+        total += 1;
+
+        if passed {
+            solved += 1;
+            solution_time += result.best_move_time;
+        }
+
+        if let Some(max) = move_points.iter().map(|&(_, p)| p).max() {
+            points += move_points
+                .iter()
+                .find(|&&(m, _)| m == mov)
+                .map_or(0, |&(_, p)| p);
+            max_points += max;
+        }
+
+        let id = record
+            .op("id")
+            .and_then(|v| v.first())
+            .map_or_else(|| format!("line {}", idx + 1), String::clone);
+
+        println!(
+            "info string {id} {} move {} time {} ms",
+            if passed { "passed" } else { "failed" },
+            record.pos.conv_mov_to_san(mov),
+            result.best_move_time,
+        );
+    }
+
+    if total == 0 {
+        println!("info string no positions found");
+        return;
+    }
+
+    print!(
+        "info string solved {solved} of {total} ({:.1}%) average solution time {} ms",
+        100.0 * solved as f32 / total as f32,
+        solution_time.checked_div(solved).unwrap_or(0),
+    );
+
+    if max_points > 0 {
+        print!(" points {points} of {max_points}");
+    }
+
+    println!();
 }
 
 fn run_perft(commands: &[&str], pos: &ChessState) {
@@ -428,7 +1174,11 @@ fn handle_search_input(abort: &AtomicBool) -> Option<String> {
 
         match input.as_str().trim() {
             "isready" => println!("readyok"),
-            "quit" => std::process::exit(0),
+            // handled once the search has stopped
+            "quit" => {
+                abort.store(true, Ordering::Relaxed);
+                return Some(input);
+            }
             "stop" => {
                 abort.store(true, Ordering::Relaxed);
                 return None;
@@ -0,0 +1,399 @@
+//! Front-end for the Chess Engine Communication Protocol (CECP) used by
+//! XBoard and WinBoard, entered from `uci::run` when the first command
+//! is `xboard`.
+
+use crate::{
+    chess::{consts::Flag, ChessState, Move},
+    mcts::{Limits, MctsParams, SearchHelpers, SearchOptions, SearchResult, Searcher},
+    networks::{PolicyNetwork, ValueNetwork},
+    pool,
+    score::Score,
+    tree::Tree,
+};
+
+use std::{
+    collections::VecDeque,
+    io, process,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+/// Commands that may arrive during a search without interrupting it, and
+/// are handled once it finishes.
+const DEFERRED: [&str; 10] = [
+    "ping", "time", "otim", "post", "nopost", "hard", "easy", "computer", "name", "rating",
+];
+
+/// Time control given by `level` or `st`.
+#[derive(Clone, Copy)]
+enum TimeControl {
+    /// Moves per session (0 for the whole game) and increment in ms, with
+    /// the remaining time given by `time` before each move.
+    Clock { mps: u64, inc: u64 },
+    /// Fixed time per move in ms.
+    MoveTime(u128),
+}
+
+struct Engine<'a> {
+    policy: &'a PolicyNetwork,
+    value: &'a ValueNetwork,
+    params: MctsParams,
+    options: SearchOptions,
+    tree: Tree,
+    threads: usize,
+    start: ChessState,
+    moves: Vec<Move>,
+    pos: ChessState,
+    /// Side the engine plays, `None` in force mode.
+    engine_side: Option<usize>,
+    analyzing: bool,
+    post: bool,
+    time_control: TimeControl,
+    max_depth: usize,
+    /// Remaining time in ms, from `time`.
+    time_left: Option<u64>,
+    pending: VecDeque<String>,
+}
+
+pub fn run(policy: &PolicyNetwork, value: &ValueNetwork) {
+    let mut engine = Engine {
+        policy,
+        value,
+        params: MctsParams::default(),
+        options: SearchOptions::default(),
+        tree: Tree::new_mb(64, 1),
+        threads: 1,
+        start: ChessState::default(),
+        moves: Vec::new(),
+        pos: ChessState::default(),
+        engine_side: Some(1),
+        analyzing: false,
+        post: false,
+        time_control: TimeControl::MoveTime(5000),
+        max_depth: 256,
+        time_left: None,
+        pending: VecDeque::new(),
+    };
+
+    loop {
+        let line = match engine.pending.pop_front() {
+            Some(line) => line,
+            None => read_line(),
+        };
+
+        engine.handle(line.trim());
+    }
+}
+
+fn read_line() -> String {
+    let mut input = String::new();
+    let bytes_read = io::stdin().read_line(&mut input).unwrap();
+
+    if bytes_read == 0 {
+        process::exit(0);
+    }
+
+    input
+}
+
+/// Moves as sent to the GUI: coordinates, except for castling in
+/// Chess960, which is `O-O` or `O-O-O`.
+fn move_str(pos: &ChessState, mov: Move) -> String {
+    if pos.castling().is_chess960() && [Flag::KS, Flag::QS].contains(&mov.flag()) {
+        pos.conv_mov_to_san(mov)
+    } else {
+        pos.conv_mov_to_str(mov)
+    }
+}
+
+impl Engine<'_> {
+    fn handle(&mut self, line: &str) {
+        let args = line.split_whitespace().collect::<Vec<_>>();
+
+        match args[..] {
+            ["protover", ..] => {
+                println!(
+                    "feature myname=\"{}\" setboard=1 usermove=1 ping=1 analyze=1 colors=0 \
+                     sigint=0 sigterm=0 san=0 memory=1 smp=1 \
+                     variants=\"normal,fischerandom\" done=1",
+                    env!("FORMATTED_NAME")
+                );
+            }
+            ["new"] => {
+                self.set_position(ChessState::default());
+                self.engine_side = Some(1);
+                self.max_depth = 256;
+                self.tree.clear(self.threads);
+            }
+            ["force"] => self.engine_side = None,
+            ["go"] => {
+                self.engine_side = Some(self.pos.stm());
+                self.think();
+            }
+            ["playother"] => self.engine_side = Some(self.pos.stm() ^ 1),
+            ["usermove", mov] => self.user_move(mov),
+            ["setboard", ..] => match ChessState::try_from_fen(&args[1..].join(" ")) {
+                Ok(pos) => {
+                    self.set_position(pos);
+
+                    if self.analyzing {
+                        self.analyze();
+                    }
+                }
+                Err(err) => println!("tellusererror Illegal position: {err}"),
+            },
+            // base time is `minutes` or `minutes:seconds`
+            ["level", mps, base, inc] => {
+                let (minutes, seconds) = base.split_once(':').unwrap_or((base, "0"));
+                let base =
+                    minutes.parse::<f64>().unwrap_or(0.0) * 60.0 + seconds.parse().unwrap_or(0.0);
+
+                self.time_control = TimeControl::Clock {
+                    mps: mps.parse().unwrap_or(0),
+                    inc: (inc.parse::<f64>().unwrap_or(0.0) * 1000.0) as u64,
+                };
+                self.time_left = Some((base * 1000.0) as u64);
+            }
+            ["st", seconds] => {
+                let seconds = seconds.parse::<f64>().unwrap_or(5.0);
+                self.time_control = TimeControl::MoveTime((seconds * 1000.0) as u128);
+            }
+            ["sd", depth] => self.max_depth = depth.parse().unwrap_or(256),
+            // in centiseconds
+            ["time", time] => self.time_left = time.parse::<u64>().ok().map(|t| t * 10),
+            ["ping", n] => println!("pong {n}"),
+            ["post"] => self.post = true,
+            ["nopost"] => self.post = false,
+            ["undo"] => self.undo(1),
+            ["remove"] => self.undo(2),
+            ["analyze"] => {
+                self.analyzing = true;
+                self.analyze();
+            }
+            ["exit"] => self.analyzing = false,
+            ["memory", mb] => self.tree = Tree::new_mb(mb.parse().unwrap_or(64), self.threads),
+            ["cores", n] => {
+                self.threads = n.parse().map_or(self.threads, |t: usize| t.clamp(1, 512));
+                pool::resize(self.threads);
+            }
+            ["result", ..] => self.engine_side = None,
+            ["quit"] => process::exit(0),
+            ["xboard" | "accepted" | "rejected" | "variant" | "otim" | "hard" | "easy", ..]
+            | ["computer" | "name" | "rating" | "random" | "draw" | "?" | ".", ..]
+            | [] => {}
+            // moves without `usermove`, for protocol version 1
+            [mov] if self.parse_move(mov).is_some() => self.user_move(mov),
+            _ => println!("Error (unknown command): {line}"),
+        }
+    }
+
+    fn parse_move(&self, mov: &str) -> Option<Move> {
+        self.pos.parse_uci(mov).or_else(|| self.pos.parse_san(mov))
+    }
+
+    fn set_position(&mut self, pos: ChessState) {
+        self.start = pos.clone();
+        self.pos = pos;
+        self.moves.clear();
+    }
+
+    fn user_move(&mut self, mov: &str) {
+        let Some(mov) = self.parse_move(mov) else {
+            println!("Illegal move: {mov}");
+            return;
+        };
+
+        self.moves.push(mov);
+        self.pos.make_move(mov);
+
+        if self.analyzing {
+            self.analyze();
+        } else if self.engine_side == Some(self.pos.stm()) {
+            self.think();
+        }
+    }
+
+    fn undo(&mut self, plies: usize) {
+        let keep = self.moves.len().saturating_sub(plies);
+        self.moves.truncate(keep);
+        self.pos = self.start.clone();
+
+        for &mov in &self.moves {
+            self.pos.make_move(mov);
+        }
+
+        if self.analyzing {
+            self.analyze();
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        let (opt_time, max_time) = match self.time_control {
+            TimeControl::MoveTime(time) => (None, Some(time)),
+            TimeControl::Clock { mps, inc } => {
+                let remaining = self.time_left.unwrap_or(60_000);
+                let our_moves = (self.moves.len() / 2) as u64;
+                let movestogo = (mps > 0).then(|| mps - our_moves % mps);
+
+                let (opt, max) = SearchHelpers::get_time(
+                    remaining,
+                    (inc > 0).then_some(inc),
+                    self.moves.len() as u32,
+                    movestogo,
+                    10,
+                    &self.params,
+                );
+
+                (Some(opt), Some(max))
+            }
+        };
+
+        Limits {
+            max_time,
+            opt_time,
+            max_depth: self.max_depth,
+            ..Default::default()
+        }
+    }
+
+    /// Search for our move and play it, unless interrupted by a command
+    /// other than `?` (move now).
+    fn think(&mut self) {
+        let mut no_moves = true;
+        self.pos.map_legal_moves(|_| no_moves = false);
+
+        if no_moves {
+            return;
+        }
+
+        let limits = self.limits();
+        self.tree.set_root_position_quiet(&self.pos);
+
+        if let Some((result, nodes, time)) = self.search(limits, false) {
+            let mov = result.best_move;
+
+            if self.post {
+                print_thinking(&result, nodes, time, self.options.cp_100_q);
+            }
+
+            println!("move {}", move_str(&self.pos, mov));
+            self.moves.push(mov);
+            self.pos.make_move(mov);
+        }
+    }
+
+    /// Search the current position until interrupted, showing the best line
+    /// every second.
+    fn analyze(&mut self) {
+        self.tree.set_root_position_quiet(&self.pos);
+        self.search(Limits::default(), true);
+    }
+
+    /// Runs a search on a separate thread while reading commands, returning
+    /// the result, nodes searched and time taken in ms, or `None` if it was
+    /// interrupted by a command that should be handled instead.
+    fn search(&mut self, limits: Limits, analyzing: bool) -> Option<(SearchResult, usize, u128)> {
+        let abort = AtomicBool::new(false);
+        let interrupted = AtomicBool::new(false);
+        let done = AtomicBool::new(false);
+        let timer = Instant::now();
+
+        let (tree, params, options) = (&self.tree, &self.params, &self.options);
+        let (policy, value, threads, post) = (self.policy, self.value, self.threads, self.post);
+        let pending = &mut self.pending;
+
+        std::thread::scope(|s| {
+            let handle = s.spawn(|| {
+                let searcher = Searcher::new(tree, params, options, policy, value, &abort);
+                let mut nodes = 0;
+
+                let result = if analyzing {
+                    // search in slices, so that each can report its best line
+                    let slice = Limits {
+                        max_time: Some(1000),
+                        ..limits
+                    };
+
+                    loop {
+                        let result = searcher.search(threads, slice, false, &mut nodes);
+
+                        if interrupted.load(Ordering::Relaxed) {
+                            break result;
+                        }
+
+                        // the end of each slice also sets `abort`
+                        abort.store(false, Ordering::Relaxed);
+
+                        if post {
+                            let time = timer.elapsed().as_millis();
+                            print_thinking(&result, nodes, time, options.cp_100_q);
+                        }
+                    }
+                } else {
+                    searcher.search(threads, limits, false, &mut nodes)
+                };
+
+                done.store(true, Ordering::Relaxed);
+
+                let time = timer.elapsed().as_millis();
+                (!interrupted.load(Ordering::Relaxed)).then_some((result, nodes, time))
+            });
+
+            loop {
+                let line = read_line();
+                let command = line.split_whitespace().next().unwrap_or("");
+
+                match command {
+                    "?" => {
+                        if !analyzing {
+                            abort.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    "." => {}
+                    _ => {
+                        if !DEFERRED.contains(&command) && !done.load(Ordering::Relaxed) {
+                            interrupted.store(true, Ordering::Relaxed);
+                            abort.store(true, Ordering::Relaxed);
+                        }
+
+                        let finished = interrupted.load(Ordering::Relaxed);
+                        pending.push_back(line);
+
+                        if finished {
+                            break;
+                        }
+                    }
+                }
+
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+            }
+
+            handle.join().unwrap()
+        })
+    }
+}
+
+/// Thinking output, `<depth> <score> <time> <nodes> <pv>`, with the score
+/// in centipawns and the time in centiseconds.
+fn print_thinking(result: &SearchResult, nodes: usize, time: u128, cp_100_q: f32) {
+    let Some(line) = result.variations(1).pop() else {
+        return;
+    };
+
+    let score = match line.score {
+        Score::Q(_) => line.score.cp_with(cp_100_q) as i32,
+        Score::Mate(n) if n > 0 => 100_000 + n,
+        Score::Mate(n) => -100_000 + n,
+    };
+
+    let pv = line
+        .moves
+        .iter()
+        .map(|mov| mov.uci.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    println!("{} {score} {} {nodes} {pv}", line.moves.len(), time / 10);
+}